@@ -0,0 +1,34 @@
+//! Records what rtool is built from for `cargo rtool -version`: the commit, and the
+//! rustc it is built with, as rtool only works with the nightly it links against.
+
+use std::env;
+use std::path::Path;
+use std::process::Command;
+
+fn main() {
+    let rustc = env::var("RUSTC").unwrap_or_else(|_| "rustc".to_string());
+    let rustc_version = output(Command::new(rustc).arg("-V")).unwrap_or("unknown rustc".into());
+    println!("cargo:rustc-env=RUSTC_VERSION={}", rustc_version);
+
+    let git_hash = output(Command::new("git").args(["rev-parse", "--short", "HEAD"]))
+        .unwrap_or("unknown commit".into());
+    println!("cargo:rustc-env=GIT_HASH={}", git_hash);
+
+    // A commit moves the branch HEAD points at, not HEAD itself.
+    println!("cargo:rerun-if-changed=build.rs");
+    for path in [".git/HEAD", ".git/refs/heads", ".git/packed-refs"] {
+        if Path::new(path).exists() {
+            println!("cargo:rerun-if-changed={}", path);
+        }
+    }
+    println!("cargo:rerun-if-env-changed=RUSTC");
+}
+
+/// The trimmed stdout of `cmd`, if it succeeds.
+fn output(cmd: &mut Command) -> Option<String> {
+    let output = cmd.output().ok()?;
+    if !output.status.success() {
+        return None;
+    }
+    Some(String::from_utf8_lossy(&output.stdout).trim().to_string())
+}
@@ -0,0 +1,60 @@
+/*
+    Register a custom analysis on top of rtool.
+
+    The analysis counts the MIR bodies of the compiled crate. Without arguments, it
+    compiles a small generated crate; otherwise the arguments are passed to rustc:
+        cargo run --example count_bodies -- path/to/lib.rs --crate-type lib
+*/
+#![feature(rustc_private)]
+
+extern crate rustc_driver;
+extern crate rustc_middle;
+
+use rtool::{RTOOL_DEFAULT_ARGS, RtoolCallback, rtool_info, utils::log::init_log};
+use rustc_middle::ty::TyCtxt;
+use std::env;
+use std::fs;
+use std::sync::atomic::{AtomicUsize, Ordering};
+
+static BODY_COUNT: AtomicUsize = AtomicUsize::new(0);
+
+const SAMPLE_CRATE: &str = r#"
+pub fn add(a: u32, b: u32) -> u32 {
+    a + b
+}
+
+pub fn double(a: u32) -> u32 {
+    add(a, a)
+}
+"#;
+
+fn count_bodies(tcx: TyCtxt<'_>) {
+    let count = tcx.mir_keys(()).len();
+    rtool_info!("{} MIR bodies", count);
+    BODY_COUNT.fetch_add(count, Ordering::SeqCst);
+}
+
+fn main() {
+    _ = init_log().inspect_err(|err| eprintln!("Failed to init log: {err}"));
+
+    let mut args: Vec<String> = env::args().collect();
+    if args.len() == 1 {
+        let dir = env::temp_dir().join("rtool_count_bodies");
+        fs::create_dir_all(&dir).expect("Failed to create the sample crate folder.");
+        let src = dir.join("lib.rs");
+        fs::write(&src, SAMPLE_CRATE).expect("Failed to write the sample crate.");
+        args.push(src.to_string_lossy().into_owned());
+        args.extend(["--crate-type", "lib", "--out-dir"].map(String::from));
+        args.push(dir.to_string_lossy().into_owned());
+    }
+    args.splice(1..1, RTOOL_DEFAULT_ARGS.iter().map(ToString::to_string));
+
+    let mut callback = RtoolCallback::default();
+    callback.register_analysis("count_bodies", Box::new(count_bodies));
+    rustc_driver::run_compiler(&args, &mut callback);
+
+    assert!(
+        BODY_COUNT.load(Ordering::SeqCst) > 0,
+        "the registered analysis did not run"
+    );
+}
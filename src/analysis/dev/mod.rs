@@ -22,7 +22,7 @@ impl<'tcx> LockDevTool<'tcx> {
             let name = self.tcx.def_path_str(did);
             if name.contains("interrupt_enable") {
                 rtool_info!("{}", name);
-                irq_api.push(did.clone());
+                irq_api.push(did);
             }
             count += 1;
         }
@@ -41,20 +41,20 @@ impl<'tcx> LockDevTool<'tcx> {
                         .stmt_at(loc) // Either<&Statement, &Terminator>
                         .right() // Right should be Terminator
                         .unwrap();
-                    if let TerminatorKind::Call { ref func, .. } = terminator.kind {
-                        if let Some((callee_id, generics)) = func.const_fn_def() {
-                            let ty_env = TypingEnv::post_analysis(self.tcx, did);
-                            if let Ok(Some(instance)) =
-                                Instance::try_resolve(self.tcx, ty_env, callee_id, generics)
-                            {
-                                let instance_id = instance.def_id();
-                                if irq_api.contains(&instance_id) {
-                                    rtool_info!(
-                                        "{} calls {}",
-                                        self.tcx.def_path_str(did),
-                                        self.tcx.def_path_str(instance_id)
-                                    );
-                                }
+                    if let TerminatorKind::Call { ref func, .. } = terminator.kind
+                        && let Some((callee_id, generics)) = func.const_fn_def()
+                    {
+                        let ty_env = TypingEnv::post_analysis(self.tcx, did);
+                        if let Ok(Some(instance)) =
+                            Instance::try_resolve(self.tcx, ty_env, callee_id, generics)
+                        {
+                            let instance_id = instance.def_id();
+                            if irq_api.contains(&instance_id) {
+                                rtool_info!(
+                                    "{} calls {}",
+                                    self.tcx.def_path_str(did),
+                                    self.tcx.def_path_str(instance_id)
+                                );
                             }
                         }
                     }
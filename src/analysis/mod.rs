@@ -1,2 +1,3 @@
 pub mod dev;
+pub mod serve;
 pub mod show_mir;
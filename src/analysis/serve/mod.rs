@@ -0,0 +1,102 @@
+use std::io::{self, BufRead, Write};
+
+use crate::analysis::show_mir::{ColorChoice, MirOutput, catch_panic, fetch_mir, render_mir_plain};
+use crate::{rtool_error, rtool_info};
+use rustc_hir::def_id::DefId;
+use rustc_middle::ty::TyCtxt;
+use serde_json::{Value, json};
+
+pub mod protocol;
+
+use protocol::{INVALID_PARAMS, METHOD_NOT_FOUND, Request};
+
+/// Answer JSON-RPC requests on stdin until EOF, once the analysis of the crate is done.
+///
+/// Requests are handled inside the compiler callback, so every query runs against the
+/// live `TyCtxt` of the crate and nothing needs to be recompiled between requests.
+///
+/// Methods:
+///     dumpMir {def_path}  the plain MIR dump of the function with this def path
+pub struct MirServer<'tcx> {
+    pub tcx: TyCtxt<'tcx>,
+}
+
+impl<'tcx> MirServer<'tcx> {
+    pub fn new(tcx: TyCtxt<'tcx>) -> Self {
+        Self { tcx }
+    }
+
+    pub fn start(&self) {
+        rtool_info!("Serve requests on stdin");
+        self.serve(io::stdin().lock(), io::stdout().lock());
+    }
+
+    fn serve(&self, input: impl BufRead, mut output: impl Write) {
+        for line in input.lines() {
+            let line = match line {
+                Ok(line) => line,
+                Err(e) => {
+                    rtool_error!("Failed to read request: {}", e);
+                    return;
+                }
+            };
+            if line.trim().is_empty() {
+                continue;
+            }
+            let response = match protocol::parse_request(&line) {
+                Ok(request) if request.notification => {
+                    self.handle(&request);
+                    continue;
+                }
+                Ok(request) => self.handle(&request),
+                Err(response) => response,
+            };
+            if let Err(e) = writeln!(output, "{}", response).and_then(|_| output.flush()) {
+                rtool_error!("Failed to write response: {}", e);
+                return;
+            }
+        }
+    }
+
+    fn handle(&self, request: &Request) -> Value {
+        match request.method.as_str() {
+            "dumpMir" => self.dump_mir(request),
+            method => protocol::error(
+                request.id.clone(),
+                METHOD_NOT_FOUND,
+                format!("unknown method {}", method),
+            ),
+        }
+    }
+
+    fn find_body_owner(&self, def_path: &str) -> Option<DefId> {
+        self.tcx
+            .mir_keys(())
+            .iter()
+            .map(|local_def_id| local_def_id.to_def_id())
+            .find(|def_id| self.tcx.def_path_str(*def_id) == def_path)
+    }
+
+    fn dump_mir(&self, request: &Request) -> Value {
+        let id = request.id.clone();
+        let Some(def_path) = request.str_param("def_path") else {
+            return protocol::error(id, INVALID_PARAMS, "missing def_path".to_string());
+        };
+        let Some(def_id) = self.find_body_owner(def_path) else {
+            return protocol::error(id, INVALID_PARAMS, format!("no MIR body for {}", def_path));
+        };
+        // The responses go to stdout, which may be a terminal.
+        let output = MirOutput {
+            color: ColorChoice::Never,
+            ..MirOutput::default()
+        };
+        let mir = fetch_mir(self.tcx, def_id).and_then(|body| {
+            catch_panic(|| render_mir_plain(self.tcx, &def_path.to_string(), body, &output))
+        });
+        let mir = match mir {
+            Ok(mir) => mir,
+            Err(e) => return protocol::error(id, INVALID_PARAMS, format!("{}: {}", def_path, e)),
+        };
+        protocol::result(id, json!({ "def_path": def_path, "mir": mir }))
+    }
+}
@@ -0,0 +1,48 @@
+//! Newline-delimited JSON-RPC 2.0: one request object per line, one response per line.
+
+use serde_json::{Value, json};
+
+pub const PARSE_ERROR: i64 = -32700;
+pub const INVALID_REQUEST: i64 = -32600;
+pub const METHOD_NOT_FOUND: i64 = -32601;
+pub const INVALID_PARAMS: i64 = -32602;
+
+pub struct Request {
+    pub id: Value,
+    pub method: String,
+    pub params: Value,
+    /// A request without an `id`, which gets no response.
+    pub notification: bool,
+}
+
+impl Request {
+    /// A string parameter given by name.
+    pub fn str_param(&self, name: &str) -> Option<&str> {
+        self.params.get(name).and_then(Value::as_str)
+    }
+}
+
+/// Parse one request line, or return the error response to send back.
+pub fn parse_request(line: &str) -> Result<Request, Value> {
+    let value: Value = serde_json::from_str(line)
+        .map_err(|e| error(Value::Null, PARSE_ERROR, format!("invalid JSON: {}", e)))?;
+    let notification = value.get("id").is_none();
+    let id = value.get("id").cloned().unwrap_or(Value::Null);
+    let Some(method) = value.get("method").and_then(Value::as_str) else {
+        return Err(error(id, INVALID_REQUEST, "missing method".to_string()));
+    };
+    Ok(Request {
+        method: method.to_string(),
+        params: value.get("params").cloned().unwrap_or(Value::Null),
+        id,
+        notification,
+    })
+}
+
+pub fn result(id: Value, result: Value) -> Value {
+    json!({ "jsonrpc": "2.0", "id": id, "result": result })
+}
+
+pub fn error(id: Value, code: i64, message: String) -> Value {
+    json!({ "jsonrpc": "2.0", "id": id, "error": { "code": code, "message": message } })
+}
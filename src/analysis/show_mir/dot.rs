@@ -0,0 +1,46 @@
+//! The `-mirdot` output: one Graphviz digraph of the basic blocks per matched function.
+
+use rustc_middle::mir::Body;
+
+fn escape(s: &str) -> String {
+    s.replace('\\', "\\\\").replace('"', "\\\"")
+}
+
+pub fn render_mir_dot(fn_name: &str, body: &Body) -> String {
+    let mut s = format!("digraph \"{}\" {{\n", escape(fn_name));
+    s += "    node [shape=box];\n";
+    for (bb, data) in body.basic_blocks.iter_enumerated() {
+        let terminator = data
+            .terminator
+            .as_ref()
+            .map_or("<no terminator>", |term| term.kind.name());
+        let style = if data.is_cleanup {
+            ", style=dashed"
+        } else {
+            ""
+        };
+        s += &format!(
+            "    bb{} [label=\"{:?}\\n{} statements\\n{}\"{}];\n",
+            bb.as_usize(),
+            bb,
+            data.statements.len(),
+            terminator,
+            style
+        );
+        for target in data.terminator.iter().flat_map(|term| term.successors()) {
+            let style = if body.basic_blocks[target].is_cleanup {
+                " [style=dashed]"
+            } else {
+                ""
+            };
+            s += &format!(
+                "    bb{} -> bb{}{};\n",
+                bb.as_usize(),
+                target.as_usize(),
+                style
+            );
+        }
+    }
+    s += "}\n";
+    s
+}
@@ -0,0 +1,43 @@
+//! The `-mirjson` output: one JSON object per line for each matched function.
+
+use rustc_hir::def_id::{DefId, LOCAL_CRATE};
+use rustc_middle::mir::Body;
+use rustc_middle::ty::TyCtxt;
+use serde_json::{Value, json};
+
+pub fn render_mir_json<'tcx>(tcx: TyCtxt<'tcx>, def_id: DefId, body: &Body<'tcx>) -> Value {
+    let smap = tcx.sess.source_map();
+    let locals: Vec<Value> = body
+        .local_decls
+        .iter_enumerated()
+        .map(|(local, decl)| json!({ "index": local.as_usize(), "ty": decl.ty.to_string() }))
+        .collect();
+    let basic_blocks: Vec<Value> = body
+        .basic_blocks
+        .iter_enumerated()
+        .map(|(bb, data)| {
+            let statements: Vec<String> = data
+                .statements
+                .iter()
+                .map(|stmt| format!("{:?}", stmt))
+                .collect();
+            let terminator = data.terminator.as_ref();
+            let span = super::bb_span(data).map(|span| smap.span_to_diagnostic_string(span));
+            json!({
+                "index": bb.as_usize(),
+                "cleanup": data.is_cleanup,
+                "statements": statements,
+                "terminator": terminator.map(|term| format!("{:?}", term.kind)),
+                "span": span,
+            })
+        })
+        .collect();
+    json!({
+        "crate": tcx.crate_name(LOCAL_CRATE).as_str(),
+        "def_path": tcx.def_path_str(def_id),
+        "def_id_index": def_id.index.as_usize(),
+        "item": super::display_const_item(tcx, def_id),
+        "locals": locals,
+        "basic_blocks": basic_blocks,
+    })
+}
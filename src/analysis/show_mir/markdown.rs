@@ -0,0 +1,57 @@
+//! The `-mirformat md` output: one Markdown section per matched function, with its
+//! locals as a table and each basic block as a fenced code block.
+
+use super::{DisplayWithTcx, bb_span, display_const_item, local_names};
+use rustc_middle::mir::Body;
+use rustc_middle::ty::TyCtxt;
+
+/// Escape the characters that would break a table cell or be taken as Markdown or HTML.
+fn escape(s: &str) -> String {
+    let mut escaped = String::with_capacity(s.len());
+    for c in s.chars() {
+        match c {
+            '<' => escaped += "&lt;",
+            '>' => escaped += "&gt;",
+            '&' => escaped += "&amp;",
+            '\\' | '|' | '*' | '_' | '`' | '[' | ']' => {
+                escaped.push('\\');
+                escaped.push(c);
+            }
+            _ => escaped.push(c),
+        }
+    }
+    escaped
+}
+
+pub fn render_mir_md<'tcx>(tcx: TyCtxt<'tcx>, fn_name: &str, body: &Body<'tcx>) -> String {
+    let smap = tcx.sess.source_map();
+    let names = local_names(body);
+    let mut s = format!("### fn {}\n\n", escape(fn_name));
+    if let Some(item) = display_const_item(tcx, body.source.def_id()) {
+        s += &format!("{}\n\n", escape(&item));
+    }
+    s += "| local | name | type |\n";
+    s += "| --- | --- | --- |\n";
+    for (local, decl) in body.local_decls.iter_enumerated() {
+        let name = names.get(&local).map(|names| names.join(", "));
+        s += &format!(
+            "| {} | {} | {} |\n",
+            escape(&format!("{:?}", local)),
+            escape(name.as_deref().unwrap_or_default()),
+            escape(&decl.ty.to_string())
+        );
+    }
+    for (bb, data) in body.basic_blocks.iter_enumerated() {
+        let cleanup = if data.is_cleanup { "(cleanup) " } else { "" };
+        s += &format!("\n{}**{:?}**", cleanup, bb);
+        if let Some(span) = bb_span(data) {
+            s += &format!(" at {}", escape(&smap.span_to_diagnostic_string(span)));
+        }
+        s += &format!(
+            "\n\n```\n{}```\n",
+            data.display_with_tcx(tcx, &body.local_decls)
+        );
+    }
+    s += "\n";
+    s
+}
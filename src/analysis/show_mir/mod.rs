@@ -1,16 +1,43 @@
-use std::fs::File;
-use std::io::{self, Write};
+mod dot;
+mod json;
+mod markdown;
+mod phase;
+mod stats;
+
+pub use phase::{
+    BodyPhase, ForgetKeptBodies, fetch_mir_in_phase, forget_kept_bodies, keep_early_phases,
+};
+pub use stats::ShowMirStats;
+
+use std::env;
+use std::fs::{self, File, OpenOptions};
+use std::hash::{DefaultHasher, Hash, Hasher};
+use std::io::{self, IsTerminal, Write};
+use std::mem;
+use std::panic::{self, AssertUnwindSafe};
 use std::path::Path;
+use std::str::FromStr;
+use std::sync::{Arc, LazyLock, Mutex, PoisonError};
 
-use crate::{rtool_error, rtool_info};
+use crate::utils::dumpfmt;
+use crate::{rtool_error, rtool_info, rtool_warn};
 use colorful::{Color, Colorful};
-use rustc_data_structures::fx::FxHashSet;
-use rustc_hir::def_id::DefId;
+use regex::Regex;
+use rustc_data_structures::fx::{FxHashMap, FxHashSet};
+use rustc_hir::BodyOwnerKind;
+use rustc_hir::def::DefKind;
+use rustc_hir::def_id::{DefId, LOCAL_CRATE, LocalDefId};
+use rustc_middle::mir::mono::MonoItem;
 use rustc_middle::mir::{
-    BasicBlockData, BasicBlocks, Body, LocalDecl, LocalDecls, Operand, Rvalue, Statement,
-    StatementKind, Terminator, TerminatorKind,
+    BasicBlockData, BasicBlocks, Body, Const, ConstValue, Local, LocalDecl, LocalDecls, Location,
+    Operand, Rvalue, START_BLOCK, Statement, StatementKind, Terminator, TerminatorKind,
+    UnwindAction, VarDebugInfoContents,
 };
-use rustc_middle::ty::{self, TyCtxt, TyKind};
+use rustc_middle::ty::{
+    self, AdtDef, EarlyBinder, GenericParamDefKind, Instance, InstanceKind, Ty, TyCtxt, TyKind,
+    TypingEnv,
+};
+use rustc_span::{FileName, Span};
 
 const NEXT_LINE: &str = "\n";
 const PADDING: &str = "    ";
@@ -21,44 +48,277 @@ pub trait Display {
     fn display(&self) -> String;
 }
 
-impl<'tcx> Display for Terminator<'tcx> {
-    fn display(&self) -> String {
-        let mut s = String::new();
-        s += &format!("{}{:?}{}", PADDING, self.kind, self.kind.display());
-        s
+/// Warn about a MIR variant the display has no arm for, once per `variant` key.
+fn warn_unknown_variant(what: &str, variant: String, shown: &str) {
+    static WARNED: LazyLock<Mutex<FxHashSet<String>>> = LazyLock::new(Default::default);
+    let mut warned = WARNED.lock().unwrap_or_else(PoisonError::into_inner);
+    if warned.insert(format!("{} {}", what, variant)) {
+        rtool_warn!("Unknown {}, shown as Unknown({})", what, shown);
     }
 }
 
-impl<'tcx> Display for TerminatorKind<'tcx> {
-    fn display(&self) -> String {
-        let mut s = String::new();
-        s += EXPLAIN;
-        match &self {
-            TerminatorKind::Goto { .. } => s += "Goto",
-            TerminatorKind::SwitchInt { .. } => s += "SwitchInt",
-            TerminatorKind::Return => s += "Return",
-            TerminatorKind::Unreachable => s += "Unreachable",
-            TerminatorKind::Drop { .. } => s += "Drop",
-            TerminatorKind::Assert { .. } => s += "Assert",
-            TerminatorKind::Yield { .. } => s += "Yield",
-            TerminatorKind::FalseEdge { .. } => s += "FalseEdge",
-            TerminatorKind::FalseUnwind { .. } => s += "FalseUnwind",
-            TerminatorKind::InlineAsm { .. } => s += "InlineAsm",
-            TerminatorKind::UnwindResume => s += "UnwindResume",
-            TerminatorKind::UnwindTerminate(..) => s += "UnwindTerminate",
-            TerminatorKind::CoroutineDrop => s += "CoroutineDrop",
-            TerminatorKind::Call { func, .. } => match func {
-                Operand::Constant(constant) => match constant.ty().kind() {
-                    ty::FnDef(id, ..) => {
-                        s += &format!("Call: FnDid: {}", id.index.as_usize()).as_str()
-                    }
-                    _ => (),
-                },
-                _ => (),
-            },
-            TerminatorKind::TailCall { .. } => todo!(),
+/// Like `Display`, for MIR naming other items or the types of locals.
+pub trait DisplayWithTcx<'tcx> {
+    fn display_with_tcx(&self, tcx: TyCtxt<'tcx>, local_decls: &LocalDecls<'tcx>) -> String;
+}
+
+impl<'tcx> DisplayWithTcx<'tcx> for Terminator<'tcx> {
+    fn display_with_tcx(&self, tcx: TyCtxt<'tcx>, local_decls: &LocalDecls<'tcx>) -> String {
+        display_terminator(self, tcx, local_decls, None)
+    }
+}
+
+/// Like `Terminator::display_with_tcx`, with the enum a `SwitchInt` switches on if
+/// known, see `switched_enum`.
+fn display_terminator<'tcx>(
+    terminator: &Terminator<'tcx>,
+    tcx: TyCtxt<'tcx>,
+    local_decls: &LocalDecls<'tcx>,
+    switched: Option<AdtDef<'tcx>>,
+) -> String {
+    let mut s = String::new();
+    s += &format!(
+        "{}{:?}{}",
+        PADDING,
+        terminator.kind,
+        display_terminator_kind(&terminator.kind, tcx, local_decls, switched)
+    );
+    s
+}
+
+impl<'tcx> DisplayWithTcx<'tcx> for TerminatorKind<'tcx> {
+    fn display_with_tcx(&self, tcx: TyCtxt<'tcx>, local_decls: &LocalDecls<'tcx>) -> String {
+        display_terminator_kind(self, tcx, local_decls, None)
+    }
+}
+
+fn display_terminator_kind<'tcx>(
+    kind: &TerminatorKind<'tcx>,
+    tcx: TyCtxt<'tcx>,
+    local_decls: &LocalDecls<'tcx>,
+    switched: Option<AdtDef<'tcx>>,
+) -> String {
+    let mut s = String::new();
+    s += EXPLAIN;
+    match kind {
+        TerminatorKind::Goto { .. } => s += "Goto",
+        TerminatorKind::SwitchInt { .. } => s += "SwitchInt",
+        TerminatorKind::Return => s += "Return",
+        TerminatorKind::Unreachable => s += "Unreachable",
+        TerminatorKind::Drop { place, .. } => {
+            s += &format!("Drop({:?}: {})", place, place.ty(local_decls, tcx).ty);
+        }
+        TerminatorKind::Assert { .. } => s += "Assert",
+        TerminatorKind::Yield { .. } => s += "Yield",
+        TerminatorKind::FalseEdge { .. } => s += "FalseEdge",
+        TerminatorKind::FalseUnwind { .. } => s += "FalseUnwind",
+        TerminatorKind::InlineAsm { .. } => s += "InlineAsm",
+        TerminatorKind::UnwindResume => s += "UnwindResume",
+        TerminatorKind::UnwindTerminate(..) => s += "UnwindTerminate",
+        TerminatorKind::CoroutineDrop => s += "CoroutineDrop",
+        TerminatorKind::Call { func, .. } => {
+            s += &format!("Call: {}", display_callee(func, tcx, local_decls));
+        }
+        TerminatorKind::TailCall { func, .. } => {
+            s += &format!("TailCall: {}", display_callee(func, tcx, local_decls));
+        }
+    };
+    s += &display_successors(kind, tcx, local_decls, switched);
+    s
+}
+
+/// Where control flow goes after a terminator, e.g. ` -> bb3` for a goto or
+/// ` -> [0: bb2, otherwise: bb5]`, labeled like rustc's MIR dumps. An unwind action
+/// other than a cleanup block is shown by its name, e.g. `unwind: continue`.
+/// The values of a `SwitchInt` are decoded, see `display_switch_value`.
+fn display_successors<'tcx>(
+    kind: &TerminatorKind<'tcx>,
+    tcx: TyCtxt<'tcx>,
+    local_decls: &LocalDecls<'tcx>,
+    switched: Option<AdtDef<'tcx>>,
+) -> String {
+    let mut successors: Vec<String> = match kind {
+        TerminatorKind::SwitchInt { discr, targets } => {
+            let ty = discr.ty(local_decls, tcx);
+            targets
+                .iter()
+                .map(|(value, bb)| {
+                    let value = display_switch_value(tcx, ty, switched, value);
+                    format!("{}: {:?}", value, bb)
+                })
+                .chain([format!("otherwise: {:?}", targets.otherwise())])
+                .collect()
+        }
+        _ => kind
+            .fmt_successor_labels()
+            .iter()
+            .zip(kind.successors())
+            .map(|(label, bb)| match label.as_ref() {
+                "" => format!("{:?}", bb),
+                label => format!("{}: {:?}", label, bb),
+            })
+            .collect(),
+    };
+    match kind.unwind() {
+        Some(UnwindAction::Continue) => successors.push("unwind: continue".to_string()),
+        Some(UnwindAction::Unreachable) => successors.push("unwind: unreachable".to_string()),
+        Some(UnwindAction::Terminate(_)) => successors.push("unwind: terminate".to_string()),
+        Some(UnwindAction::Cleanup(_)) | None => {}
+    }
+    match successors.as_slice() {
+        [] => String::new(),
+        [bb] if !bb.contains(':') => format!(" -> {}", bb),
+        _ => format!(" -> [{}]", successors.join(", ")),
+    }
+}
+
+/// A value a `SwitchInt` compares its operand of type `ty` with: the variant of the
+/// `switched` enum with this discriminant, `false` or `true`, a char, or a signed
+/// integer, rather than the raw bits.
+fn display_switch_value<'tcx>(
+    tcx: TyCtxt<'tcx>,
+    ty: Ty<'tcx>,
+    switched: Option<AdtDef<'tcx>>,
+    value: u128,
+) -> String {
+    if let Some(adt) = switched
+        && let Some((variant, _)) = adt.discriminants(tcx).find(|(_, discr)| discr.val == value)
+    {
+        return adt.variant(variant).name.to_string();
+    }
+    match ty.kind() {
+        TyKind::Bool if value <= 1 => (value == 1).to_string(),
+        TyKind::Char => match u32::try_from(value).ok().and_then(char::from_u32) {
+            Some(c) => format!("{:?}", c),
+            None => value.to_string(),
+        },
+        TyKind::Int(_) => ty.primitive_size(tcx).sign_extend(value).to_string(),
+        _ => value.to_string(),
+    }
+}
+
+/// The enum whose discriminant the `SwitchInt` ending `bb` switches on, if it is read
+/// in the same block, as rustc lowers `match`.
+fn switched_enum<'tcx>(
+    tcx: TyCtxt<'tcx>,
+    local_decls: &LocalDecls<'tcx>,
+    bb: &BasicBlockData<'tcx>,
+) -> Option<AdtDef<'tcx>> {
+    let TerminatorKind::SwitchInt { discr, .. } = &bb.terminator.as_ref()?.kind else {
+        return None;
+    };
+    let discr = discr.place()?;
+    bb.statements.iter().rev().find_map(|stmt| {
+        let StatementKind::Assign(assign) = &stmt.kind else {
+            return None;
         };
-        s
+        match &**assign {
+            (place, Rvalue::Discriminant(switched)) if *place == discr => {
+                match switched.ty(local_decls, tcx).ty.kind() {
+                    TyKind::Adt(adt, _) if adt.is_enum() => Some(*adt),
+                    _ => None,
+                }
+            }
+            _ => None,
+        }
+    })
+}
+
+/// The path and generic args of a direct callee, or the type of an indirect one.
+fn display_callee<'tcx>(
+    func: &Operand<'tcx>,
+    tcx: TyCtxt<'tcx>,
+    local_decls: &LocalDecls<'tcx>,
+) -> String {
+    match func.const_fn_def() {
+        Some((id, args)) => tcx.def_path_str_with_args(id, args),
+        None => format!("indirect {}", func.ty(local_decls, tcx)),
+    }
+}
+
+/// The operands of a `Use`, `Repeat` or `BinaryOp` rvalue with their constants
+/// decoded, e.g. `: Add(copy _3, const 2_i32)`, see `display_const`. Empty for other
+/// rvalues and for operands without constants.
+fn display_rvalue_operands<'tcx>(rvalue: &Rvalue<'tcx>, tcx: TyCtxt<'tcx>) -> String {
+    let is_const = |operand: &Operand<'_>| operand.constant().is_some();
+    match rvalue {
+        Rvalue::Use(operand) | Rvalue::Repeat(operand, _) if !is_const(operand) => String::new(),
+        Rvalue::BinaryOp(_, operands) if !is_const(&operands.0) && !is_const(&operands.1) => {
+            String::new()
+        }
+        Rvalue::Use(operand) => format!(": {}", display_operand(operand, tcx)),
+        Rvalue::Repeat(operand, count) => {
+            format!(": [{}; {}]", display_operand(operand, tcx), count)
+        }
+        Rvalue::BinaryOp(op, operands) => format!(
+            ": {:?}({}, {})",
+            op,
+            display_operand(&operands.0, tcx),
+            display_operand(&operands.1, tcx)
+        ),
+        _ => String::new(),
+    }
+}
+
+fn display_operand<'tcx>(operand: &Operand<'tcx>, tcx: TyCtxt<'tcx>) -> String {
+    match operand {
+        Operand::Constant(constant) => match constant.ty().kind() {
+            TyKind::FnDef(..) => display_const(constant.const_, tcx),
+            _ => format!("const {}", display_const(constant.const_, tcx)),
+        },
+        _ => format!("{:?}", operand),
+    }
+}
+
+/// The longest constant shown in full by `display_const`.
+const MAX_CONST_LEN: usize = 48;
+
+/// The value of a constant: ints with their type suffix, bools, chars, string
+/// literals and the types of zero-sized values, or `<unevaluated>` for a constant
+/// CTFE would have to evaluate first. Other values, e.g. aggregates, are shown as
+/// rustc prints them, cut short after `MAX_CONST_LEN` chars.
+fn display_const<'tcx>(constant: Const<'tcx>, tcx: TyCtxt<'tcx>) -> String {
+    let ty = constant.ty();
+    let value = match constant {
+        Const::Unevaluated(..) => return "<unevaluated>".to_string(),
+        Const::Ty(_, ct) => match ct.kind() {
+            ty::ConstKind::Value(_) => None,
+            ty::ConstKind::Param(param) => return param.to_string(),
+            _ => return "<unevaluated>".to_string(),
+        },
+        Const::Val(ConstValue::ZeroSized, _) => Some(match ty.kind() {
+            TyKind::FnDef(def_id, args) => tcx.def_path_str_with_args(*def_id, args),
+            _ => ty.to_string(),
+        }),
+        Const::Val(value @ (ConstValue::Slice { .. } | ConstValue::Indirect { .. }), _)
+            if ty.peel_refs().is_str() =>
+        {
+            value
+                .try_get_slice_bytes_for_diagnostics(tcx)
+                .map(|bytes| format!("{:?}", String::from_utf8_lossy(bytes)))
+        }
+        Const::Val(..) => None,
+    };
+    let value = value.or_else(|| {
+        let int = constant.try_to_scalar_int()?;
+        match ty.kind() {
+            TyKind::Bool => Some(format!("{}", constant.try_to_bool()?)),
+            TyKind::Char => Some(format!("{:?}", char::from_u32(int.to_u32())?)),
+            TyKind::Int(int_ty) => {
+                Some(format!("{}_{}", int.to_int(int.size()), int_ty.name_str()))
+            }
+            TyKind::Uint(uint_ty) => Some(format!(
+                "{}_{}",
+                int.to_uint(int.size()),
+                uint_ty.name_str()
+            )),
+            _ => None,
+        }
+    });
+    let value = value.unwrap_or_else(|| constant.to_string());
+    match value.char_indices().nth(MAX_CONST_LEN) {
+        Some((end, _)) => format!("{}...", &value[..end]),
+        None => value,
     }
 }
 
@@ -70,6 +330,18 @@ impl<'tcx> Display for Statement<'tcx> {
     }
 }
 
+/// Like `Statement::display`, with the constants of an assignment decoded, see
+/// `display_rvalue_operands`.
+impl<'tcx> DisplayWithTcx<'tcx> for Statement<'tcx> {
+    fn display_with_tcx(&self, tcx: TyCtxt<'tcx>, _local_decls: &LocalDecls<'tcx>) -> String {
+        let mut s = self.display();
+        if let StatementKind::Assign(assign) = &self.kind {
+            s += &display_rvalue_operands(&assign.1, tcx);
+        }
+        s
+    }
+}
+
 impl<'tcx> Display for StatementKind<'tcx> {
     fn display(&self) -> String {
         let mut s = String::new();
@@ -90,7 +362,10 @@ impl<'tcx> Display for StatementKind<'tcx> {
             StatementKind::PlaceMention(..) => s += "PlaceMention",
             StatementKind::Intrinsic(..) => s += "Intrinsic",
             StatementKind::ConstEvalCounter => s += "ConstEvalCounter",
-            _ => todo!(),
+            _ => {
+                warn_unknown_variant("statement kind", self.name().to_string(), self.name());
+                s += &format!("Unknown({})", self.name());
+            }
         }
         s
     }
@@ -115,21 +390,26 @@ impl<'tcx> Display for Rvalue<'tcx> {
             Rvalue::ShallowInitBox(..) => s += "ShallowInitBox",
             Rvalue::CopyForDeref(..) => s += "CopyForDeref",
             Rvalue::RawPtr(_, _) => s += "RawPtr",
-            _ => todo!(),
+            _ => {
+                let shown = format!("{:?}", self);
+                let variant = format!("{:?}", mem::discriminant(self));
+                warn_unknown_variant("rvalue", variant, &shown);
+                s += &format!("Unknown({})", shown);
+            }
         }
         s
     }
 }
 
-impl<'tcx> Display for BasicBlocks<'tcx> {
-    fn display(&self) -> String {
+impl<'tcx> DisplayWithTcx<'tcx> for BasicBlocks<'tcx> {
+    fn display_with_tcx(&self, tcx: TyCtxt<'tcx>, local_decls: &LocalDecls<'tcx>) -> String {
         let mut s = String::new();
         for (index, bb) in self.iter().enumerate() {
             s += &format!(
-                "bb {} {{{}{}}}{}",
-                index,
+                "{} {{{}{}}}{}",
+                display_bb_name(index, bb),
                 NEXT_LINE,
-                bb.display(),
+                bb.display_with_tcx(tcx, local_decls),
                 NEXT_LINE
             );
         }
@@ -137,18 +417,34 @@ impl<'tcx> Display for BasicBlocks<'tcx> {
     }
 }
 
-impl<'tcx> Display for BasicBlockData<'tcx> {
-    fn display(&self) -> String {
+/// The name a basic block is headed by, `bb N`, prefixed with `(cleanup)` for the
+/// blocks only run on unwind so that panic paths stand out.
+fn display_bb_name(index: usize, bb: &BasicBlockData) -> String {
+    if bb.is_cleanup {
+        format!("(cleanup) bb {}", index)
+    } else {
+        format!("bb {}", index)
+    }
+}
+
+impl<'tcx> DisplayWithTcx<'tcx> for BasicBlockData<'tcx> {
+    fn display_with_tcx(&self, tcx: TyCtxt<'tcx>, local_decls: &LocalDecls<'tcx>) -> String {
         let mut s = String::new();
         s += &format!("CleanUp: {}{}", self.is_cleanup, NEXT_LINE);
         for stmt in self.statements.iter() {
-            s += &format!("{}{}", stmt.display(), NEXT_LINE);
+            s += &format!("{}{}", stmt.display_with_tcx(tcx, local_decls), NEXT_LINE);
+        }
+        match &self.terminator {
+            Some(terminator) => {
+                let switched = switched_enum(tcx, local_decls, self);
+                s += &format!(
+                    "{}{}",
+                    display_terminator(terminator, tcx, local_decls, switched),
+                    NEXT_LINE
+                );
+            }
+            None => s += &format!("{}<no terminator>{}", PADDING, NEXT_LINE),
         }
-        s += &format!(
-            "{}{}",
-            self.terminator.clone().unwrap().display(),
-            NEXT_LINE
-        );
         s
     }
 }
@@ -163,19 +459,45 @@ impl<'tcx> Display for LocalDecls<'tcx> {
     }
 }
 
-impl<'tcx> Display for LocalDecl<'tcx> {
-    fn display(&self) -> String {
-        let mut s = String::new();
-        s += &format!("{}{}", EXPLAIN, self.ty.kind().display());
-        s
+/// The local decls of `body`, with the source names of the locals from the debug info.
+pub fn display_local_decls(body: &Body) -> String {
+    let names = local_names(body);
+    let mut s = String::new();
+    for (local, ld) in body.local_decls.iter_enumerated() {
+        match names.get(&local) {
+            Some(names) => {
+                s += &format!(
+                    "{:?}: {} // {}{}",
+                    local,
+                    ld.display(),
+                    names.join(", "),
+                    NEXT_LINE
+                )
+            }
+            None => s += &format!("{:?}: {} {}", local, ld.display(), NEXT_LINE),
+        }
+    }
+    s
+}
+
+/// The source names of the locals of `body`, from its debug info.
+fn local_names(body: &Body) -> FxHashMap<Local, Vec<String>> {
+    let mut names: FxHashMap<Local, Vec<String>> = FxHashMap::default();
+    for var in body.var_debug_info.iter() {
+        if let VarDebugInfoContents::Place(place) = var.value {
+            names
+                .entry(place.local)
+                .or_default()
+                .push(var.name.to_string());
+        }
     }
+    names
 }
 
-impl<'tcx> Display for Body<'tcx> {
+impl<'tcx> Display for LocalDecl<'tcx> {
     fn display(&self) -> String {
         let mut s = String::new();
-        s += &self.local_decls.display();
-        s += &self.basic_blocks.display();
+        s += &format!("{}{}", EXPLAIN, self.ty.kind().display());
         s
     }
 }
@@ -194,38 +516,218 @@ impl Display for DefId {
     }
 }
 
-// #[inline(always)]
-pub fn display_mir_colored(did: DefId, body: &Body) {
-    rtool_info!("{}", did.display().color(Color::LightRed));
-    rtool_info!("{}", body.local_decls.display().color(Color::Green));
-    rtool_info!(
-        "{}",
-        body.basic_blocks.display().color(Color::LightGoldenrod2a)
-    );
+/// With `generics`, the `fn` line of a generic function is followed by its generic
+/// parameters, see `display_generics`. Instances, named with their generic args, have
+/// none left.
+fn display_mir_inner<'tcx>(
+    tcx: TyCtxt<'tcx>,
+    name: &String,
+    body: &Body<'tcx>,
+    generics: bool,
+    source: bool,
+    color: bool,
+    writer: &mut dyn Write,
+) -> Result<(), io::Error> {
+    writer.write_fmt(format_args!(
+        "{}\n",
+        paint(format!("fn {}", name), Color::LightRed, color)
+    ))?;
+    if generics && let Some(generics) = display_generics(tcx, body.source.def_id()) {
+        writer.write_fmt(format_args!("generics {}\n", generics))?;
+    }
+    if let Some(item) = display_const_item(tcx, body.source.def_id()) {
+        writer.write_fmt(format_args!("{}\n", item))?;
+    }
+    display_body_inner(tcx, body, source, color, writer)
 }
 
-pub fn display_mir_plain(name: &String, body: &Body, writer: &mut Box<dyn Write>) {
-    match display_mir_plain_inner(name, body, writer) {
-        Ok(_) => {}
-        Err(e) => {
-            rtool_error!("{}", e.to_string())
+/// The kind of the const or static item whose initializer is `def_id`: `const`, `static`
+/// or `static mut`, or None for other bodies.
+pub(crate) fn const_item_kind(tcx: TyCtxt<'_>, def_id: DefId) -> Option<&'static str> {
+    match tcx.hir_body_owner_kind(def_id) {
+        // Anonymous consts, such as array lengths, are not items.
+        BodyOwnerKind::Const { inline: false } if tcx.def_kind(def_id) != DefKind::AnonConst => {
+            Some("const")
         }
+        BodyOwnerKind::Static(ty::Mutability::Not) => Some("static"),
+        BodyOwnerKind::Static(ty::Mutability::Mut) => Some("static mut"),
+        _ => None,
     }
 }
 
-fn display_mir_plain_inner(
-    name: &String,
-    body: &Body,
-    writer: &mut Box<dyn Write>,
+/// A const or static item labeled with its kind and declared type, e.g. `static
+/// spin::SpinLock<u32>`, or None for other bodies.
+fn display_const_item(tcx: TyCtxt<'_>, def_id: DefId) -> Option<String> {
+    let kind = const_item_kind(tcx, def_id)?;
+    Some(format!(
+        "{} {}",
+        kind,
+        tcx.type_of(def_id).instantiate_identity()
+    ))
+}
+
+/// The generic parameters of an item, those of its parents first, as `<'a, T, const N>`,
+/// or None if it has none.
+fn display_generics(tcx: TyCtxt<'_>, def_id: DefId) -> Option<String> {
+    let mut chain = vec![tcx.generics_of(def_id)];
+    while let Some(parent) = chain.last().and_then(|generics| generics.parent) {
+        chain.push(tcx.generics_of(parent));
+    }
+    let params: Vec<String> = chain
+        .iter()
+        .rev()
+        .flat_map(|generics| generics.own_params.iter())
+        .map(|param| match param.kind {
+            GenericParamDefKind::Const { .. } => format!("const {}", param.name),
+            GenericParamDefKind::Lifetime | GenericParamDefKind::Type { .. } => {
+                param.name.to_string()
+            }
+        })
+        .collect();
+    if params.is_empty() {
+        None
+    } else {
+        Some(format!("<{}>", params.join(", ")))
+    }
+}
+
+/// `text` colored with `color` if `enabled`.
+fn paint(text: String, color: Color, enabled: bool) -> String {
+    if enabled {
+        text.color(color).to_string()
+    } else {
+        text
+    }
+}
+
+/// Write the locals and basic blocks of a body. With `source`, each block starts with
+/// the source lines it comes from, see `display_bb_source`.
+fn display_body_inner<'tcx>(
+    tcx: TyCtxt<'tcx>,
+    body: &Body<'tcx>,
+    source: bool,
+    color: bool,
+    writer: &mut dyn Write,
 ) -> Result<(), io::Error> {
-    writer.write_fmt(format_args!("fn {}\n", name))?;
-    writer.write_fmt(format_args!("{}\n", body.local_decls.display()))?;
-    writer.write_fmt(format_args!("{}\n", body.basic_blocks.display()))?;
+    writer.write_fmt(format_args!(
+        "{}\n",
+        paint(display_local_decls(body), Color::Green, color)
+    ))?;
+    if source {
+        for (index, bb) in body.basic_blocks.iter().enumerate() {
+            writer.write_fmt(format_args!(
+                "{}{}{}{}",
+                paint(
+                    format!("{} {{{}", display_bb_name(index, bb), NEXT_LINE),
+                    Color::LightGoldenrod2a,
+                    color
+                ),
+                paint(display_bb_source(tcx, bb), Color::DarkGray, color),
+                paint(
+                    format!("{}}}", bb.display_with_tcx(tcx, &body.local_decls)),
+                    Color::LightGoldenrod2a,
+                    color
+                ),
+                NEXT_LINE
+            ))?;
+        }
+        writer.write_fmt(format_args!("\n"))?;
+    } else {
+        writer.write_fmt(format_args!(
+            "{}\n",
+            paint(
+                body.basic_blocks.display_with_tcx(tcx, &body.local_decls),
+                Color::LightGoldenrod2a,
+                color
+            )
+        ))?;
+    }
     writer.flush()
 }
 
-pub fn display_bb_source_info<'tcx>(tcx: TyCtxt<'tcx>, body: &Body, writer: &mut Box<dyn Write>) {
-    match display_bb_source_info_inner(tcx, body, writer) {
+/// The source lines a basic block comes from, each prefixed with `//`. Code expanded
+/// from macros is shown at the macro call, and code from other crates by its file only.
+fn display_bb_source(tcx: TyCtxt<'_>, bb: &BasicBlockData) -> String {
+    let smap = tcx.sess.source_map();
+    let mut spans = bb
+        .statements
+        .iter()
+        .map(|stmt| stmt.source_info.span)
+        .chain(bb.terminator.as_ref().map(|term| term.source_info.span))
+        .map(|span| span.source_callsite())
+        .filter(|span| !span.is_dummy());
+    let Some(first) = spans.next() else {
+        return String::new();
+    };
+    let file = smap.lookup_source_file(first.lo());
+    if file.cnum != LOCAL_CRATE {
+        return format!(
+            "{}// {}{}",
+            PADDING,
+            smap.filename_for_diagnostics(&file.name),
+            NEXT_LINE
+        );
+    }
+    let range = spans
+        .filter(|span| smap.lookup_source_file(span.lo()).start_pos == file.start_pos)
+        .fold(first, |range, span| range.to(span));
+    let Ok(lines) = smap.span_to_lines(range) else {
+        return String::new();
+    };
+    let mut s = String::new();
+    for line in lines.lines {
+        let text = file.get_line(line.line_index).unwrap_or_default();
+        s += &format!("{}// {}{}", PADDING, text.trim_end(), NEXT_LINE);
+    }
+    s
+}
+
+/// If `enabled`, write the immediate dominator of each basic block as `bbN idom bbM`,
+/// followed by `loop-header` if a back edge, whose source it dominates, targets it.
+/// The entry block is written as `bb0 entry` and the blocks it doesn't reach as
+/// `bbN unreachable`.
+fn display_cfg(body: &Body, enabled: bool, writer: &mut dyn Write) -> Result<(), io::Error> {
+    if !enabled {
+        return Ok(());
+    }
+    let dominators = body.basic_blocks.dominators();
+    let mut loop_headers = FxHashSet::default();
+    for (bb, data) in body.basic_blocks.iter_enumerated() {
+        if !dominators.is_reachable(bb) {
+            continue;
+        }
+        loop_headers.extend(
+            data.terminator()
+                .successors()
+                .filter(|target| dominators.dominates(*target, bb)),
+        );
+    }
+    for bb in body.basic_blocks.indices() {
+        let idom = match dominators.immediate_dominator(bb) {
+            Some(idom) => format!("idom {:?}", idom),
+            None if bb == START_BLOCK => "entry".to_string(),
+            None => "unreachable".to_string(),
+        };
+        let marker = if loop_headers.contains(&bb) {
+            " loop-header"
+        } else {
+            ""
+        };
+        writeln!(writer, "{:?} {}{}", bb, idom, marker)?;
+    }
+    Ok(())
+}
+
+/// Write where the basic blocks come from: the span of the first statement of each
+/// block, or of its terminator if it has none. With `all_spans`, write the span of every
+/// statement and terminator instead, as `bbN[i] at <span>`.
+pub fn display_bb_source_info<'tcx>(
+    tcx: TyCtxt<'tcx>,
+    body: &Body,
+    all_spans: bool,
+    writer: &mut Box<dyn Write>,
+) {
+    match display_bb_source_info_inner(tcx, body, all_spans, writer) {
         Ok(_) => {}
         Err(e) => {
             rtool_error!("{}", e.to_string());
@@ -236,64 +738,685 @@ pub fn display_bb_source_info<'tcx>(tcx: TyCtxt<'tcx>, body: &Body, writer: &mut
 fn display_bb_source_info_inner<'tcx>(
     tcx: TyCtxt<'tcx>,
     body: &Body,
-    writer: &mut Box<dyn Write>,
+    all_spans: bool,
+    writer: &mut dyn Write,
 ) -> Result<(), io::Error> {
+    let smap = tcx.sess.source_map();
     for (idx, bb) in body.basic_blocks.iter_enumerated() {
-        if bb.statements.len() == 0 {
-            continue;
+        if all_spans {
+            let spans = bb
+                .statements
+                .iter()
+                .map(|stmt| stmt.source_info.span)
+                .chain(bb.terminator.as_ref().map(|term| term.source_info.span));
+            for (statement_index, span) in spans.enumerate() {
+                let loc = Location {
+                    block: idx,
+                    statement_index,
+                };
+                writer.write_fmt(format_args!(
+                    "{:?} at {}\n",
+                    loc,
+                    smap.span_to_diagnostic_string(span)
+                ))?
+            }
+        } else if let Some(span) = bb_span(bb) {
+            writer.write_fmt(format_args!(
+                "{:?} at {}\n",
+                idx,
+                smap.span_to_diagnostic_string(span)
+            ))?
         }
-        let stmt = &bb.statements[0];
-        let span = stmt.source_info.span;
-        let smap = tcx.sess.source_map();
-        writer.write_fmt(format_args!(
-            "{:?} at {}\n",
-            idx,
-            smap.span_to_diagnostic_string(span)
-        ))?
     }
     Ok(())
 }
 
+/// Where a basic block comes from: the span of its first statement, or of its
+/// terminator if it has none.
+fn bb_span(bb: &BasicBlockData) -> Option<Span> {
+    bb.statements
+        .first()
+        .map(|stmt| stmt.source_info.span)
+        .or(bb.terminator.as_ref().map(|term| term.source_info.span))
+}
+
+/// Render the same section as `-mir` writes for one function: the plain MIR
+/// followed by the source info of its basic blocks, see `display_bb_source_info`.
+/// The MIR is colored if `MirOutput::is_colored`.
+pub fn render_mir_plain<'tcx>(
+    tcx: TyCtxt<'tcx>,
+    name: &String,
+    body: &Body<'tcx>,
+    output: &MirOutput,
+) -> String {
+    render_plain(tcx, name, body, true, output)
+}
+
+/// Like `render_mir_plain`, for a body with the generic args of an instance applied,
+/// which `name` includes.
+fn render_instance_plain<'tcx>(
+    tcx: TyCtxt<'tcx>,
+    name: &String,
+    body: &Body<'tcx>,
+    output: &MirOutput,
+) -> String {
+    render_plain(tcx, name, body, false, output)
+}
+
+fn render_plain<'tcx>(
+    tcx: TyCtxt<'tcx>,
+    name: &String,
+    body: &Body<'tcx>,
+    generics: bool,
+    output: &MirOutput,
+) -> String {
+    let mut buf: Vec<u8> = vec![];
+    let color = output.is_colored();
+    let source = output.source;
+    if let Err(e) = display_mir_inner(tcx, name, body, generics, source, color, &mut buf)
+        .and_then(|_| display_cfg(body, output.cfg, &mut buf))
+        .and_then(|_| display_bb_source_info_inner(tcx, body, output.all_spans, &mut buf))
+    {
+        rtool_error!("{}", e.to_string());
+    }
+    String::from_utf8_lossy(&buf).into_owned()
+}
+
+/// Render the bodies promoted out of a function, each under a `promoted[N]` line and
+/// laid out like the function itself.
+pub fn render_promoted_plain(tcx: TyCtxt<'_>, def_id: DefId, output: &MirOutput) -> String {
+    let mut buf: Vec<u8> = vec![];
+    let color = output.is_colored();
+    for (promoted, body) in tcx.promoted_mir(def_id).iter_enumerated() {
+        if let Err(e) = writeln!(buf, "{:?}", promoted)
+            .and_then(|_| display_body_inner(tcx, body, output.source, color, &mut buf))
+            .and_then(|_| display_cfg(body, output.cfg, &mut buf))
+            .and_then(|_| display_bb_source_info_inner(tcx, body, output.all_spans, &mut buf))
+        {
+            rtool_error!("{}", e.to_string());
+        }
+    }
+    String::from_utf8_lossy(&buf).into_owned()
+}
+
+/// Run `f`, turning a panic into an error so that one bad body doesn't abort the run.
+pub(crate) fn catch_panic<T>(f: impl FnOnce() -> T) -> Result<T, String> {
+    panic::catch_unwind(AssertUnwindSafe(f)).map_err(|payload| {
+        payload
+            .downcast_ref::<&str>()
+            .map(|msg| msg.to_string())
+            .or_else(|| payload.downcast_ref::<String>().cloned())
+            .unwrap_or_else(|| "unknown panic".to_string())
+    })
+}
+
+/// Fetch the MIR of a body owner, or the reason it can't be shown.
+///
+/// Bodies tainted by errors are refused, as their MIR is not meaningful.
+pub fn fetch_mir<'tcx>(tcx: TyCtxt<'tcx>, def_id: DefId) -> Result<&'tcx Body<'tcx>, String> {
+    if !tcx.is_mir_available(def_id) {
+        return Err("MIR is not available".to_string());
+    }
+    let body = catch_panic(|| tcx.instance_mir(ty::InstanceKind::Item(def_id)))
+        .map_err(|e| format!("failed to fetch MIR: {}", e))?;
+    if body.tainted_by_errors.is_some() {
+        return Err("the body has errors".to_string());
+    }
+    Ok(body)
+}
+
+/// Warn once per crate that bodies with errors will be skipped.
+fn warn_if_errors(tcx: TyCtxt<'_>) {
+    if tcx.dcx().has_errors().is_some() {
+        rtool_warn!("The crate has errors, MIR of the bodies with errors is skipped");
+    }
+}
+
+fn report_skipped(tcx: TyCtxt<'_>, def_id: DefId, reason: &str) {
+    rtool_warn!("Skip {}: {}", tcx.def_path_str(def_id), reason);
+}
+
+/// Hash of the displayed body, leaving out the source info: the same body seen from
+/// another crate refers to its source files by other paths.
+fn body_hash<'tcx>(tcx: TyCtxt<'tcx>, body: &Body<'tcx>) -> u64 {
+    let mut hasher = DefaultHasher::new();
+    body.local_decls.display().hash(&mut hasher);
+    body.basic_blocks
+        .display_with_tcx(tcx, &body.local_decls)
+        .hash(&mut hasher);
+    hasher.finish()
+}
+
 pub struct ShowAllMir<'tcx> {
     pub tcx: TyCtxt<'tcx>,
+    /// If the MIR written to stdout is colored.
+    pub color: ColorChoice,
+    /// Only show the bodies under these module prefixes, or all if empty.
+    pub filters: Vec<String>,
+    /// Also show the initializers of consts and statics, see `-allmir-include-consts`.
+    pub include_consts: bool,
 }
 
 impl<'tcx> ShowAllMir<'tcx> {
-    pub fn new(tcx: TyCtxt<'tcx>) -> Self {
-        Self { tcx }
+    pub fn new(
+        tcx: TyCtxt<'tcx>,
+        color: ColorChoice,
+        filters: &[String],
+        include_consts: bool,
+    ) -> Self {
+        Self {
+            tcx,
+            color,
+            filters: filters.to_vec(),
+            include_consts,
+        }
+    }
+
+    /// If the def path of a body, or of the module it is declared in, starts with one of
+    /// the filters. Matching the module keeps the methods of impls and the trait impls
+    /// for foreign types declared in it, whose def paths start with the self type.
+    fn is_selected(&self, def_id: LocalDefId, name: &str) -> bool {
+        if self.filters.is_empty() {
+            return true;
+        }
+        let module = self.tcx.parent_module_from_def_id(def_id).to_def_id();
+        let module = self.tcx.def_path_str(module);
+        self.filters
+            .iter()
+            .any(|prefix| name.starts_with(prefix.as_str()) || module.starts_with(prefix.as_str()))
     }
 
     pub fn start(&mut self) {
         rtool_info!("Show all MIR");
-        let mir_keys = self.tcx.mir_keys(());
-        for each_mir in mir_keys {
-            let def_id = each_mir.to_def_id();
-            let body = self.tcx.instance_mir(ty::InstanceKind::Item(def_id));
-            display_mir_colored(def_id, body);
+        warn_if_errors(self.tcx);
+        // Sort by path, `mir_keys` isn't ordered the same way across runs.
+        let mut def_ids: Vec<(DefId, String)> = self
+            .tcx
+            .mir_keys(())
+            .iter()
+            .map(|each_mir| (*each_mir, self.tcx.def_path_str(each_mir.to_def_id())))
+            .filter(|(each_mir, name)| self.is_selected(*each_mir, name))
+            .map(|(each_mir, name)| (each_mir.to_def_id(), name))
+            .filter(|(def_id, _)| {
+                self.include_consts || const_item_kind(self.tcx, *def_id).is_none()
+            })
+            .collect();
+        def_ids.sort_by(|a, b| a.1.cmp(&b.1));
+        let output = MirOutput {
+            color: self.color,
+            ..MirOutput::default()
+        };
+        let mut stdout = io::stdout().lock();
+        for (def_id, name) in def_ids {
+            let res = fetch_mir(self.tcx, def_id)
+                .and_then(|body| catch_panic(|| render_mir_plain(self.tcx, &name, body, &output)));
+            let text = match res {
+                Ok(text) => text,
+                Err(e) => {
+                    report_skipped(self.tcx, def_id, &e);
+                    continue;
+                }
+            };
+            // Stop once stdout is gone, e.g. closed by `| head`, rather than failing
+            // for each of the remaining bodies.
+            match stdout.write_all(text.as_bytes()) {
+                Ok(()) => {}
+                Err(e) if e.kind() == io::ErrorKind::BrokenPipe => return,
+                Err(e) => {
+                    rtool_error!("Failed to write the MIR to stdout: {}", e);
+                    return;
+                }
+            }
+        }
+    }
+}
+
+/// The format `FindAndShowMir` writes matched functions in.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum MirFormat {
+    /// Sections described in `utils::dumpfmt`.
+    Plain,
+    /// One JSON object per line, see `-mirjson`.
+    Json,
+    /// One Graphviz digraph per function, see `-mirdot`.
+    Dot,
+    /// One Markdown section per function, see `-mirformat md`.
+    Markdown,
+    /// One line per function without its MIR, followed by the number of functions,
+    /// see `-mirlist`.
+    List,
+}
+
+impl FromStr for MirFormat {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "plain" => Ok(MirFormat::Plain),
+            "json" => Ok(MirFormat::Json),
+            "dot" => Ok(MirFormat::Dot),
+            "md" => Ok(MirFormat::Markdown),
+            _ => Err(format!(
+                "unknown mir format {}, expected plain, json, dot or md",
+                s
+            )),
+        }
+    }
+}
+
+/// When MIR in the plain format is colored, see `-color`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Default)]
+pub enum ColorChoice {
+    Always,
+    Never,
+    /// Only on a terminal, unless a non-empty `NO_COLOR` environment variable is set.
+    #[default]
+    Auto,
+}
+
+impl ColorChoice {
+    /// If MIR written to a terminal, or elsewhere if `terminal` is false, is colored.
+    pub fn is_enabled(self, terminal: bool) -> bool {
+        match self {
+            ColorChoice::Always => true,
+            ColorChoice::Never => false,
+            ColorChoice::Auto => {
+                terminal && env::var_os("NO_COLOR").is_none_or(|value| value.is_empty())
+            }
+        }
+    }
+}
+
+impl FromStr for ColorChoice {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "always" => Ok(ColorChoice::Always),
+            "never" => Ok(ColorChoice::Never),
+            "auto" => Ok(ColorChoice::Auto),
+            _ => Err(format!(
+                "unknown color choice {}, expected always, never or auto",
+                s
+            )),
+        }
+    }
+}
+
+/// Where and how `FindAndShowMir` writes matched functions.
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub struct MirOutput {
+    /// Write to this file instead of stdout.
+    pub file: Option<String>,
+    /// Write each function to its own file in this folder instead.
+    pub dir: Option<String>,
+    /// Append to `file` with a per-crate header, so that several crates
+    /// compiled in one cargo-rtool run can share the same file.
+    pub append: bool,
+    /// In append mode, write a `ref` line for functions another crate of the run has
+    /// already dumped with the same body.
+    pub dedup: bool,
+    /// The manifest the crates of a cargo-rtool run record their dumps in, see
+    /// `dumpfmt::claim_section`. There is no dedup without one.
+    pub manifest: Option<String>,
+    pub format: MirFormat,
+    /// In the plain format, also show the bodies promoted out of each function.
+    pub promoted: bool,
+    /// In the plain format, show the span of every statement, see
+    /// `display_bb_source_info`.
+    pub all_spans: bool,
+    /// In the plain format, start each basic block with its source lines, see
+    /// `display_bb_source`.
+    pub source: bool,
+    /// In the plain format, follow the basic blocks with their dominators, see
+    /// `display_cfg`.
+    pub cfg: bool,
+    /// Follow each generic function with its instances, see
+    /// `FindAndShowMir::instances`.
+    pub instances: bool,
+    pub phase: BodyPhase,
+    /// If the plain format is colored, which only files written with
+    /// `ColorChoice::Always` are.
+    pub color: ColorChoice,
+}
+
+impl Default for MirOutput {
+    fn default() -> Self {
+        Self {
+            file: None,
+            dir: None,
+            append: false,
+            dedup: true,
+            manifest: None,
+            format: MirFormat::Plain,
+            promoted: false,
+            all_spans: false,
+            source: false,
+            cfg: false,
+            instances: false,
+            phase: BodyPhase::Optimized,
+            color: ColorChoice::Auto,
+        }
+    }
+}
+
+impl MirOutput {
+    /// If MIR in the plain format is colored, depending on where it is written.
+    pub fn is_colored(&self) -> bool {
+        let terminal = self.file.is_none() && self.dir.is_none() && io::stdout().is_terminal();
+        self.color.is_enabled(terminal)
+    }
+    /// Write the output of an analysis to `file`, or to stdout.
+    ///
+    /// The output of a crate is written at once under a lock, so that the crates
+    /// compiled concurrently by cargo-rtool don't interleave their output. `header` is
+    /// written first unless appending to a file which isn't empty.
+    pub fn write(&self, header: &str, out: &[u8]) {
+        match self.file {
+            Some(ref path) => {
+                if let Err(e) = self.write_file(path, header, out) {
+                    rtool_error!(
+                        "Failed to write output file {}: {}, writing to stdout instead",
+                        path,
+                        e
+                    );
+                    Self::write_stdout(header, out);
+                }
+            }
+            None => Self::write_stdout(header, out),
+        }
+    }
+
+    fn write_stdout(header: &str, out: &[u8]) {
+        let mut stdout = io::stdout().lock();
+        if let Err(e) = stdout
+            .write_all(header.as_bytes())
+            .and_then(|_| stdout.write_all(out))
+        {
+            rtool_error!("{}", e.to_string());
+        }
+    }
+
+    fn write_file(&self, path: &str, header: &str, out: &[u8]) -> Result<(), io::Error> {
+        let mut file = if self.append {
+            OpenOptions::new().append(true).create(true).open(path)?
+        } else {
+            File::create(path)?
+        };
+        file.lock()?;
+        if file.metadata()?.len() == 0 {
+            file.write_all(header.as_bytes())?;
         }
+        file.write_all(out)?;
+        file.flush()
+    }
+}
+
+/// The file `-outdir` writes a function to: `<crate>.<def path>.<extension>`, with the
+/// `::` of the def path turned into dots and other characters unfit for file names
+/// into underscores. Overlong names are cut and end with a hash of the def path.
+pub fn mir_file_name(crate_name: &str, fn_name: &str, format: MirFormat) -> String {
+    const MAX_STEM_LEN: usize = 200;
+    let extension = match format {
+        MirFormat::Plain => "txt",
+        MirFormat::Json => "json",
+        MirFormat::Dot => "dot",
+        MirFormat::Markdown => "md",
+        MirFormat::List => "txt",
+    };
+    let mut stem: String = format!("{}.{}", crate_name, fn_name.replace("::", "."))
+        .chars()
+        .map(|c| {
+            if c.is_ascii_alphanumeric() || matches!(c, '.' | '_' | '-') {
+                c
+            } else {
+                '_'
+            }
+        })
+        .collect();
+    if stem.len() > MAX_STEM_LEN {
+        let mut hasher = DefaultHasher::new();
+        fn_name.hash(&mut hasher);
+        stem.truncate(MAX_STEM_LEN - 17);
+        stem += &format!("-{:016x}", hasher.finish());
     }
+    format!("{}.{}", stem, extension)
 }
 
 pub struct FindAndShowMir<'tcx, 'a> {
     pub tcx: TyCtxt<'tcx>,
     pub exact_fn_names: &'a Vec<String>,
     pub fuzzy_fn_names: &'a Vec<String>,
-    pub output_file: Option<String>,
+    pub fuzzy_case: MirCase,
+    /// `-mirdefid` targets, as parsed by `parse_mir_def_id`.
+    pub def_ids: &'a [(u32, u32)],
+    /// Matched against the whole def path.
+    pub regexes: Vec<Regex>,
+    /// Source lines, as parsed by `parse_mir_location`.
+    pub locations: &'a [(String, usize)],
+    /// The bodies spanning `locations`, found by `start`.
+    located: FxHashSet<DefId>,
+    /// Also show the initializers of the consts and statics of the crate, see
+    /// `-mirconsts`.
+    pub consts: bool,
+    pub output: MirOutput,
+}
+
+/// A `-mirexact` target matches the def path.
+fn is_exact_match(target: &str, fn_name: &str) -> bool {
+    target == fn_name
+}
+
+/// A `-mirexact` target matching a part of the `DefId` rather than the def path, as it
+/// used to. Deprecated in favor of `-mirdefid`, and warned about by
+/// `FindAndShowMir::start`.
+fn is_def_id_part_match(target: &str, def_id: DefId) -> bool {
+    format!("{:?}", def_id).contains(target)
+}
+
+/// A `-mirdefid` target matches the crate number and def index of the `DefId`.
+fn is_def_id_match(target: (u32, u32), def_id: DefId) -> bool {
+    target == (def_id.krate.as_u32(), def_id.index.as_u32())
+}
+
+/// A `-mir` target matches a part of the last segment of the def path.
+fn is_fuzzy_match(target: &str, fn_name: &str, case: MirCase) -> bool {
+    let last = fn_name.split("::").last().unwrap_or("");
+    match case {
+        MirCase::Sensitive => last.contains(target),
+        MirCase::Insensitive => last.to_lowercase().contains(&target.to_lowercase()),
+    }
+}
+
+/// How `-mir` targets compare letters, see `-mircase`. `-mirexact` is always case
+/// sensitive.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Default)]
+pub enum MirCase {
+    #[default]
+    Sensitive,
+    /// Both the target and the last segment of the def path are lowercased, so that
+    /// `irqhandler` matches `IrqHandler`, `IRQHandler` and `irqhandler_entry`.
+    Insensitive,
+}
+
+impl FromStr for MirCase {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "sensitive" => Ok(MirCase::Sensitive),
+            "insensitive" => Ok(MirCase::Insensitive),
+            _ => Err(format!(
+                "unknown case {}, expected sensitive or insensitive",
+                s
+            )),
+        }
+    }
+}
+
+/// Name the targets of `FindAndShowMir` by the options giving them, for reports.
+pub fn target_names(
+    exact_fn_names: &[String],
+    fuzzy_fn_names: &[String],
+    def_ids: &[(u32, u32)],
+    regex_patterns: &[String],
+    locations: &[(String, usize)],
+) -> Vec<String> {
+    let exact = exact_fn_names.iter().map(|t| format!("-mirexact {}", t));
+    let fuzzy = fuzzy_fn_names.iter().map(|t| format!("-mir {}", t));
+    let def_id = def_ids
+        .iter()
+        .map(|(krate, index)| format!("-mirdefid {}:{}", krate, index));
+    let regex = regex_patterns.iter().map(|t| format!("-mirregex {}", t));
+    let at = locations
+        .iter()
+        .map(|(file, line)| format!("-mirat {}:{}", file, line));
+    exact
+        .chain(fuzzy)
+        .chain(def_id)
+        .chain(regex)
+        .chain(at)
+        .collect()
+}
+
+/// Parse a `-mirdefid` target, `<crate>:<index>` as in `DefId(0:5 ~ krate::f)`.
+pub fn parse_mir_def_id(def_id: &str) -> Result<(u32, u32), String> {
+    def_id
+        .split_once(':')
+        .and_then(|(krate, index)| Some((krate.parse().ok()?, index.parse().ok()?)))
+        .ok_or_else(|| format!("expected <crate>:<index>, e.g. 0:5, got {}", def_id))
+}
+
+/// Split a `-mir`/`-mirexact` value into its comma-separated fn names, trimmed. Commas
+/// inside `<>`, `()` or `[]`, as in `HashMap::<K, V>::get`, don't separate names, and
+/// neither does an escaped `\,`.
+pub fn split_fn_names(value: &str) -> Result<Vec<String>, String> {
+    let mut names = vec![];
+    let mut name = String::new();
+    let mut depth = 0usize;
+    let mut chars = value.chars();
+    while let Some(c) = chars.next() {
+        match c {
+            '\\' if chars.clone().next() == Some(',') => {
+                name.push(',');
+                chars.next();
+            }
+            '<' | '(' | '[' => {
+                depth += 1;
+                name.push(c);
+            }
+            '>' | ')' | ']' => {
+                depth = depth.saturating_sub(1);
+                name.push(c);
+            }
+            ',' if depth == 0 => names.push(mem::take(&mut name)),
+            _ => name.push(c),
+        }
+    }
+    names.push(name);
+    names
+        .into_iter()
+        .map(|name| {
+            let name = name.trim();
+            if name.is_empty() {
+                Err(format!("empty fn name in {}", value))
+            } else {
+                Ok(name.to_string())
+            }
+        })
+        .collect()
+}
+
+/// Parse a `-mirat` location, `<file>:<line>`.
+pub fn parse_mir_location(location: &str) -> Result<(String, usize), String> {
+    location
+        .rsplit_once(':')
+        .and_then(|(file, line)| Some((file.to_string(), line.parse().ok()?)))
+        .filter(|(file, line)| !file.is_empty() && *line > 0)
+        .ok_or_else(|| format!("expected <file>:<line>, got {}", location))
 }
 
 impl<'tcx, 'a> FindAndShowMir<'tcx, 'a> {
+    /// Invalid `regex_patterns` are reported and skipped.
+    #[allow(clippy::too_many_arguments)]
     pub fn new(
         tcx: TyCtxt<'tcx>,
         exact_fn_names: &'a Vec<String>,
         fuzzy_fn_names: &'a Vec<String>,
-        output_file: Option<String>,
+        fuzzy_case: MirCase,
+        def_ids: &'a [(u32, u32)],
+        regex_patterns: &[String],
+        locations: &'a [(String, usize)],
+        consts: bool,
+        output: MirOutput,
     ) -> Self {
+        let regexes = regex_patterns
+            .iter()
+            .filter_map(|pattern| {
+                Regex::new(pattern)
+                    .inspect_err(|e| rtool_error!("Skip invalid regex {}: {}", pattern, e))
+                    .ok()
+            })
+            .collect();
         Self {
             tcx,
             exact_fn_names,
             fuzzy_fn_names,
-            output_file,
+            fuzzy_case,
+            def_ids,
+            regexes,
+            locations,
+            located: FxHashSet::default(),
+            consts,
+            output,
+        }
+    }
+
+    /// The local bodies spanning `line` of `file`, or None if no source file of the
+    /// crate is `file`. A relative `file` matches the files whose path ends with it.
+    fn bodies_at(&self, file: &str, line: usize) -> Option<Vec<DefId>> {
+        let smap = self.tcx.sess.source_map();
+        let target = Path::new(file);
+        let target = if target.is_absolute() {
+            fs::canonicalize(target).unwrap_or_else(|_| target.to_path_buf())
+        } else {
+            target.to_path_buf()
+        };
+        let files: Vec<_> = smap
+            .files()
+            .iter()
+            .filter(|source_file| source_file.cnum == LOCAL_CRATE)
+            .filter(|source_file| {
+                let FileName::Real(ref name) = source_file.name else {
+                    return false;
+                };
+                name.local_path().is_some_and(|path| {
+                    path.ends_with(&target)
+                        || (target.is_absolute()
+                            && fs::canonicalize(path).is_ok_and(|path| path == target))
+                })
+            })
+            .cloned()
+            .collect();
+        if files.is_empty() {
+            return None;
         }
+        let bodies = self
+            .tcx
+            .mir_keys(())
+            .iter()
+            .filter(|local_def_id| {
+                let hir_id = self.tcx.local_def_id_to_hir_id(**local_def_id);
+                let span = self.tcx.hir_span_with_body(hir_id).source_callsite();
+                let lo = smap.lookup_char_pos(span.lo());
+                let hi = smap.lookup_char_pos(span.hi());
+                files.iter().any(|file| Arc::ptr_eq(file, &lo.file))
+                    && lo.line <= line
+                    && line <= hi.line
+            })
+            .map(|local_def_id| local_def_id.to_def_id())
+            .collect();
+        Some(bodies)
     }
 
     /// Get argument count for a function (returns None if MIR not available)
@@ -302,10 +1425,10 @@ impl<'tcx, 'a> FindAndShowMir<'tcx, 'a> {
             return None;
         }
         // Skip const contexts (only applicable to local functions)
-        if let Some(local_def_id) = def_id.as_local() {
-            if self.tcx.hir_body_const_context(local_def_id).is_some() {
-                return None;
-            }
+        if let Some(local_def_id) = def_id.as_local()
+            && self.tcx.hir_body_const_context(local_def_id).is_some()
+        {
+            return None;
         }
         let body = self.tcx.optimized_mir(def_id);
         Some(body.arg_count)
@@ -329,27 +1452,21 @@ impl<'tcx, 'a> FindAndShowMir<'tcx, 'a> {
         // Traverse all basic blocks in the MIR body
         let body = self.tcx.optimized_mir(def_id);
         for bb_data in body.basic_blocks.iter() {
-            if let Some(terminator) = &bb_data.terminator {
-                if let TerminatorKind::Call { func, .. } = &terminator.kind {
-                    if let Operand::Constant(c) = func {
-                        if let ty::FnDef(callee_def_id, _) = c.ty().kind() {
-                            // Recursively collect called functions
-                            self.collect_reachable_functions(*callee_def_id, reachable);
-                        }
-                    }
-                }
+            if let Some(terminator) = &bb_data.terminator
+                && let TerminatorKind::Call { func, .. } = &terminator.kind
+                && let Operand::Constant(c) = func
+                && let ty::FnDef(callee_def_id, _) = c.ty().kind()
+            {
+                // Recursively collect called functions
+                self.collect_reachable_functions(*callee_def_id, reachable);
             }
         }
     }
 
-    pub fn start(&mut self) {
-        let mut out_writer = match self.output_file {
-            Some(ref path) => {
-                let os_path = Path::new(path);
-                Box::new(File::create(&os_path).unwrap()) as Box<dyn Write>
-            }
-            None => Box::new(io::stdout()) as Box<dyn Write>,
-        };
+    /// Show the matched functions, returning the targets which matched any, as named by
+    /// `target_names`.
+    pub fn start(&mut self) -> Vec<String> {
+        warn_if_errors(self.tcx);
         // Collect all reachable functions with available MIR
         let mir_keys = self.tcx.mir_keys(());
         let mut reachable_functions = FxHashSet::default();
@@ -359,33 +1476,436 @@ impl<'tcx, 'a> FindAndShowMir<'tcx, 'a> {
             self.collect_reachable_functions(local_def_id.to_def_id(), &mut reachable_functions);
         }
 
-        // Convert to Vec for iteration
-        let reachable_vec: Vec<DefId> = reachable_functions.iter().copied().collect();
+        // Convert to Vec for iteration, in a stable order
+        let mut reachable_vec: Vec<DefId> = reachable_functions.iter().copied().collect();
+        reachable_vec.sort_by_cached_key(|def_id| self.tcx.def_path_str(*def_id));
 
         rtool_info!("Exact match target: {:?}", { self.exact_fn_names });
-        rtool_info!("Fuzzy match target: {:?}", { self.fuzzy_fn_names });
-        for def_id in reachable_vec {
-            let fn_name = self.tcx.def_path_str(def_id);
-            let def_id_str = format!("{:?}", def_id);
-            // rtool_info!("Checking {}", fn_name);
-            if self
-                .exact_fn_names
+        match self.fuzzy_case {
+            MirCase::Sensitive => rtool_info!("Fuzzy match target: {:?}", { self.fuzzy_fn_names }),
+            MirCase::Insensitive => rtool_info!("Fuzzy match target, case insensitive: {:?}", {
+                self.fuzzy_fn_names
+            }),
+        }
+        rtool_info!("DefId match target: {:?}", { self.def_ids });
+        rtool_info!("Regex match target: {:?}", {
+            self.regexes.iter().map(Regex::as_str).collect::<Vec<_>>()
+        });
+        let mut located_at = vec![];
+        for (file, line) in self.locations.iter() {
+            match self.bodies_at(file, *line) {
+                None => rtool_info!("{} is not part of this crate", file),
+                Some(bodies) if bodies.is_empty() => {}
+                Some(bodies) => {
+                    self.located.extend(bodies.iter().copied());
+                    located_at.push(((file.clone(), *line), bodies));
+                }
+            }
+        }
+        let mut matches: Vec<(DefId, String)> = reachable_vec
+            .into_iter()
+            .map(|def_id| (def_id, self.tcx.def_path_str(def_id)))
+            .filter(|(def_id, fn_name)| self.is_match(*def_id, fn_name))
+            .collect();
+        self.warn_def_id_part_matches(&matches);
+        // Consts and statics are const contexts, which are never reachable.
+        if self.consts {
+            matches.extend(
+                mir_keys
+                    .iter()
+                    .map(|local_def_id| local_def_id.to_def_id())
+                    .filter(|def_id| const_item_kind(self.tcx, *def_id).is_some())
+                    .map(|def_id| (def_id, self.tcx.def_path_str(def_id))),
+            );
+        }
+        // The closures, coroutines and inline consts of a matched body go along with it,
+        // their def paths naming the body they are nested in. They are looked up in
+        // `mir_keys`, as inline consts are not reachable.
+        let matched: FxHashSet<DefId> = matches.iter().map(|(def_id, _)| *def_id).collect();
+        for local_def_id in mir_keys.iter() {
+            let def_id = local_def_id.to_def_id();
+            if self.tcx.is_typeck_child(def_id)
+                && !matched.contains(&def_id)
+                && matched.contains(&self.tcx.typeck_root_def_id(def_id))
+            {
+                matches.push((def_id, self.tcx.def_path_str(def_id)));
+            }
+        }
+        matches.sort_by(|a, b| a.1.cmp(&b.1));
+        // A location only matches if one of its bodies is shown, which consts and
+        // other bodies that are not reachable are not.
+        let shown: FxHashSet<DefId> = matches.iter().map(|(def_id, _)| *def_id).collect();
+        let matched_locations: Vec<(String, usize)> = located_at
+            .into_iter()
+            .filter(|(_, bodies)| bodies.iter().any(|def_id| shown.contains(def_id)))
+            .map(|(location, _)| location)
+            .collect();
+
+        self.show_matches(&matches);
+        target_names(
+            &self.exact_targets_matching(&matches),
+            &self.fuzzy_targets_matching(&matches),
+            &self.def_id_targets_matching(&matches),
+            &self.regex_targets_matching(&matches),
+            &matched_locations,
+        )
+    }
+
+    fn show_matches(&self, matches: &[(DefId, String)]) {
+        if self.output.dir.is_some() && self.output.format == MirFormat::List {
+            rtool_warn!("-outdir is ignored by -mirlist");
+        } else if let Some(ref dir) = self.output.dir {
+            if let Err(e) = fs::create_dir_all(dir) {
+                rtool_error!("Failed to create output folder {}: {}", dir, e);
+                return;
+            }
+            let instances = self.instances(matches);
+            for (def_id, fn_name) in matches.iter() {
+                self.show_mir_in_dir(Path::new(dir), *def_id, fn_name, None);
+                for (instance, name) in instances.get(def_id).into_iter().flatten() {
+                    self.show_mir_in_dir(Path::new(dir), *def_id, name, Some(*instance));
+                }
+            }
+            return;
+        }
+
+        let mut out: Vec<u8> = vec![];
+        // In append mode, cargo-rtool has already written the header.
+        let res = if self.output.format != MirFormat::Plain {
+            Ok(())
+        } else if self.output.append {
+            dumpfmt::write_crate_line(&mut out, self.tcx.crate_name(LOCAL_CRATE).as_str())
+        } else {
+            dumpfmt::write_header(&mut out)
+        };
+        if let Err(e) = res {
+            rtool_error!("{}", e.to_string());
+        }
+        let instances = self.instances(matches);
+        let mut shown = 0;
+        for (def_id, fn_name) in matches.iter() {
+            if self.show_mir(*def_id, fn_name, None, &mut out) {
+                shown += 1;
+            }
+            for (instance, name) in instances.get(def_id).into_iter().flatten() {
+                self.show_mir(*def_id, name, Some(*instance), &mut out);
+            }
+        }
+        if self.output.format == MirFormat::List
+            && let Err(e) = writeln!(out, "{} fns matched", shown)
+        {
+            rtool_error!("{}", e.to_string());
+        }
+        self.output.write("", &out);
+    }
+
+    /// If any of the exact, fuzzy, regex or location targets matches, so that a function
+    /// matching several of them is shown once.
+    fn is_match(&self, def_id: DefId, fn_name: &str) -> bool {
+        self.exact_fn_names
+            .iter()
+            .any(|target| is_exact_match(target, fn_name) || is_def_id_part_match(target, def_id))
+            || self
+                .def_ids
+                .iter()
+                .any(|target| is_def_id_match(*target, def_id))
+            || self
+                .fuzzy_fn_names
+                .iter()
+                .any(|target| is_fuzzy_match(target, fn_name, self.fuzzy_case))
+            || self.regexes.iter().any(|regex| regex.is_match(fn_name))
+            || self.located.contains(&def_id)
+    }
+
+    fn exact_targets_matching(&self, matches: &[(DefId, String)]) -> Vec<String> {
+        self.exact_fn_names
+            .iter()
+            .filter(|target| {
+                matches.iter().any(|(def_id, fn_name)| {
+                    is_exact_match(target, fn_name) || is_def_id_part_match(target, *def_id)
+                })
+            })
+            .cloned()
+            .collect()
+    }
+
+    fn def_id_targets_matching(&self, matches: &[(DefId, String)]) -> Vec<(u32, u32)> {
+        self.def_ids
+            .iter()
+            .filter(|target| {
+                matches
+                    .iter()
+                    .any(|(def_id, _)| is_def_id_match(**target, *def_id))
+            })
+            .copied()
+            .collect()
+    }
+
+    /// Warn about the `-mirexact` targets matching fns by a part of their `DefId` only.
+    fn warn_def_id_part_matches(&self, matches: &[(DefId, String)]) {
+        for target in self.exact_fn_names.iter() {
+            let names: Vec<&str> = matches
                 .iter()
-                .any(|target| *target == fn_name || def_id_str.contains(target))
+                .filter(|(def_id, fn_name)| {
+                    !is_exact_match(target, fn_name) && is_def_id_part_match(target, *def_id)
+                })
+                .map(|(_, fn_name)| fn_name.as_str())
+                .collect();
+            if !names.is_empty() {
+                rtool_warn!(
+                    "-mirexact {} matched {} by DefId rather than def path, which is \
+                     deprecated and will be dropped in the next release; use -mirdefid",
+                    target,
+                    names.join(", ")
+                );
+            }
+        }
+    }
+
+    fn fuzzy_targets_matching(&self, matches: &[(DefId, String)]) -> Vec<String> {
+        self.fuzzy_fn_names
+            .iter()
+            .filter(|target| {
+                matches
+                    .iter()
+                    .any(|(_, fn_name)| is_fuzzy_match(target, fn_name, self.fuzzy_case))
+            })
+            .cloned()
+            .collect()
+    }
+
+    fn regex_targets_matching(&self, matches: &[(DefId, String)]) -> Vec<String> {
+        self.regexes
+            .iter()
+            .filter(|regex| matches.iter().any(|(_, fn_name)| regex.is_match(fn_name)))
+            .map(|regex| regex.as_str().to_string())
+            .collect()
+    }
+
+    /// Render a function in the output format, along with its `body_hash`.
+    ///
+    /// The body is rendered before writing it, so a body failing to render leaves no
+    /// partial section in the output.
+    fn render(&self, def_id: DefId, fn_name: &String) -> Result<(String, u64), String> {
+        let body = fetch_mir_in_phase(self.tcx, def_id, self.output.phase)?;
+        catch_panic(|| {
+            let text = match self.output.format {
+                MirFormat::Plain if self.output.promoted => {
+                    render_mir_plain(self.tcx, fn_name, body, &self.output)
+                        + &render_promoted_plain(self.tcx, def_id, &self.output)
+                }
+                MirFormat::Plain => render_mir_plain(self.tcx, fn_name, body, &self.output),
+                MirFormat::Json => format!("{}\n", json::render_mir_json(self.tcx, def_id, body)),
+                MirFormat::Dot => dot::render_mir_dot(fn_name, body),
+                MirFormat::Markdown => markdown::render_mir_md(self.tcx, fn_name, body),
+                MirFormat::List => self.render_list_line(def_id, fn_name, body),
+            };
+            (text, body_hash(self.tcx, body))
+        })
+    }
+
+    /// Like `render`, for the body of a generic function with the args of `instance`
+    /// applied. The promoted bodies are left out, as they stay generic.
+    fn render_instance(
+        &self,
+        instance: Instance<'tcx>,
+        name: &String,
+    ) -> Result<(String, u64), String> {
+        let def_id = instance.def_id();
+        let body = fetch_mir_in_phase(self.tcx, def_id, self.output.phase)?;
+        catch_panic(|| {
+            let body = instance.instantiate_mir_and_normalize_erasing_regions(
+                self.tcx,
+                TypingEnv::fully_monomorphized(),
+                EarlyBinder::bind(body.clone()),
+            );
+            let text = match self.output.format {
+                MirFormat::Plain => render_instance_plain(self.tcx, name, &body, &self.output),
+                MirFormat::Json => format!("{}\n", json::render_mir_json(self.tcx, def_id, &body)),
+                MirFormat::Dot => dot::render_mir_dot(name, &body),
+                MirFormat::Markdown => markdown::render_mir_md(self.tcx, name, &body),
+                MirFormat::List => self.render_list_line(def_id, name, &body),
+            };
+            (text, body_hash(self.tcx, &body))
+        })
+    }
+
+    /// The instances of the generic functions among `matches` which the mono item
+    /// collection of the crate finds, sorted by name, if `-mirinstances` is given.
+    /// A generic function without any keeps its generic body only.
+    fn instances(
+        &self,
+        matches: &[(DefId, String)],
+    ) -> FxHashMap<DefId, Vec<(Instance<'tcx>, String)>> {
+        let mut instances: FxHashMap<DefId, Vec<(Instance<'tcx>, String)>> = FxHashMap::default();
+        if !self.output.instances {
+            return instances;
+        }
+        let generic: FxHashSet<DefId> = matches
+            .iter()
+            .map(|(def_id, _)| *def_id)
+            .filter(|def_id| {
+                self.tcx
+                    .generics_of(*def_id)
+                    .requires_monomorphization(self.tcx)
+            })
+            .collect();
+        if generic.is_empty() {
+            return instances;
+        }
+        let items = catch_panic(|| {
+            self.tcx
+                .collect_and_partition_mono_items(())
+                .codegen_units
+                .iter()
+                .flat_map(|cgu| cgu.items().keys().copied())
+                .collect::<Vec<_>>()
+        });
+        let items = match items {
+            Ok(items) => items,
+            Err(e) => {
+                rtool_warn!("Failed to collect the instances: {}", e);
+                return instances;
+            }
+        };
+        let mut seen = FxHashSet::default();
+        for item in items {
+            if let MonoItem::Fn(instance) = item
+                && let InstanceKind::Item(def_id) = instance.def
+                && generic.contains(&def_id)
+                && seen.insert(instance)
             {
-                let body = self.tcx.instance_mir(ty::InstanceKind::Item(def_id));
+                let name = self.tcx.def_path_str_with_args(def_id, instance.args);
+                instances.entry(def_id).or_default().push((instance, name));
+            }
+        }
+        for list in instances.values_mut() {
+            list.sort_by(|a, b| a.1.cmp(&b.1));
+        }
+        instances
+    }
+
+    /// The `-mirlist` line of a function: its def path, block count, location and
+    /// `DefId`, the latter last as it contains spaces.
+    fn render_list_line(&self, def_id: DefId, fn_name: &str, body: &Body<'tcx>) -> String {
+        let smap = self.tcx.sess.source_map();
+        let loc = smap.lookup_char_pos(self.tcx.def_span(def_id).lo());
+        format!(
+            "{} blocks={} at {}:{} {:?}\n",
+            fn_name,
+            body.basic_blocks.len(),
+            smap.filename_for_diagnostics(&loc.file.name),
+            loc.line,
+            def_id
+        )
+    }
+
+    /// Render a function, or one of its instances named `fn_name`.
+    fn render_any(
+        &self,
+        def_id: DefId,
+        fn_name: &String,
+        instance: Option<Instance<'tcx>>,
+    ) -> Result<(String, u64), String> {
+        match instance {
+            Some(instance) => self.render_instance(instance, fn_name),
+            None => self.render(def_id, fn_name),
+        }
+    }
+
+    /// Write a function, or one of its instances, returning false if it was skipped.
+    fn show_mir(
+        &self,
+        def_id: DefId,
+        fn_name: &String,
+        instance: Option<Instance<'tcx>>,
+        out_writer: &mut dyn Write,
+    ) -> bool {
+        match self.render_any(def_id, fn_name, instance) {
+            Ok((text, body_hash)) => {
                 rtool_info!("{}", def_id.display().color(Color::LightBlue));
-                display_bb_source_info(self.tcx, body, &mut out_writer);
-                display_mir_plain(&fn_name, body, &mut out_writer);
+                let dumped_in = match self.output.format {
+                    MirFormat::Plain => {
+                        self.dumped_in(def_id, instance.map(|_| fn_name), body_hash)
+                    }
+                    MirFormat::Json | MirFormat::Dot | MirFormat::Markdown | MirFormat::List => {
+                        None
+                    }
+                };
+                let res = match dumped_in {
+                    Some(crate_name) => {
+                        dumpfmt::write_cross_reference(out_writer, &crate_name, fn_name)
+                    }
+                    None => out_writer.write_all(text.as_bytes()),
+                };
+                if let Err(e) = res {
+                    rtool_error!("{}", e.to_string());
+                }
+                true
+            }
+            Err(e) => {
+                report_skipped(self.tcx, def_id, &e);
+                false
             }
-            if self.fuzzy_fn_names.iter().any(|fuzzy_name| {
-                let real_fn_name = fn_name.split("::").last().unwrap_or("");
-                real_fn_name.contains(fuzzy_name)
-            }) {
-                let body = self.tcx.instance_mir(ty::InstanceKind::Item(def_id));
+        }
+    }
+
+    /// Write a function, or one of its instances, to its own file in `dir`, see
+    /// `mir_file_name`.
+    fn show_mir_in_dir(
+        &self,
+        dir: &Path,
+        def_id: DefId,
+        fn_name: &String,
+        instance: Option<Instance<'tcx>>,
+    ) {
+        match self.render_any(def_id, fn_name, instance) {
+            Ok((text, _)) => {
                 rtool_info!("{}", def_id.display().color(Color::LightBlue));
-                display_bb_source_info(self.tcx, body, &mut out_writer);
-                display_mir_plain(&fn_name, body, &mut out_writer);
+                let crate_name = self.tcx.crate_name(LOCAL_CRATE);
+                let path = dir.join(mir_file_name(
+                    crate_name.as_str(),
+                    fn_name,
+                    self.output.format,
+                ));
+                let text = match self.output.format {
+                    MirFormat::Plain => format!("{}\n{}", dumpfmt::header(), text),
+                    MirFormat::Json | MirFormat::Dot | MirFormat::Markdown | MirFormat::List => {
+                        text
+                    }
+                };
+                if let Err(e) = fs::write(&path, text) {
+                    rtool_error!("Failed to write {}: {}", path.display(), e);
+                }
+            }
+            Err(e) => report_skipped(self.tcx, def_id, &e),
+        }
+    }
+
+    /// The crate which already dumped this body in the current run, if any. The
+    /// instances of a function, named by `instance`, are told apart by their name.
+    fn dumped_in(
+        &self,
+        def_id: DefId,
+        instance: Option<&String>,
+        body_hash: u64,
+    ) -> Option<String> {
+        if !self.output.dedup || !self.output.append {
+            return None;
+        }
+        let manifest = Path::new(self.output.manifest.as_ref()?);
+        let mut key = self.tcx.def_path_hash(def_id).0.to_hex();
+        if let Some(name) = instance {
+            let mut hasher = DefaultHasher::new();
+            name.hash(&mut hasher);
+            key += &format!("-{:016x}", hasher.finish());
+        }
+        let crate_name = self.tcx.crate_name(LOCAL_CRATE);
+        match dumpfmt::claim_section(manifest, &key, body_hash, crate_name.as_str()) {
+            Ok(dumped_in) => dumped_in,
+            Err(e) => {
+                rtool_error!("Failed to update {}: {}", manifest.display(), e);
+                None
             }
         }
     }
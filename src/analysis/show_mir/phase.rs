@@ -0,0 +1,139 @@
+//! The `-mirphase` option: show bodies as built or as borrow checked, instead of
+//! optimized.
+//!
+//! rustc steals the bodies of these phases as soon as the next phase is computed,
+//! mostly before rtool runs. So while `-mirphase` is set, the queries computing them
+//! are overridden to keep a copy of each body in the arena, see `keep_early_phases`.
+
+use std::cell::RefCell;
+use std::fmt;
+use std::str::FromStr;
+
+use rustc_data_structures::fx::FxHashMap;
+use rustc_data_structures::steal::Steal;
+use rustc_hir::def_id::{DefId, LocalDefId};
+use rustc_middle::mir::Body;
+use rustc_middle::ty::TyCtxt;
+use rustc_middle::util::Providers;
+
+use super::fetch_mir;
+
+/// The phase of the bodies `FindAndShowMir` shows.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum BodyPhase {
+    /// Right after lowering from THIR, `mir_built`.
+    Built,
+    /// After drop elaboration and const checking, as analyses see it,
+    /// `mir_drops_elaborated_and_const_checked`.
+    Analysis,
+    /// What codegen sees, `optimized_mir`.
+    Optimized,
+}
+
+impl FromStr for BodyPhase {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "built" => Ok(BodyPhase::Built),
+            "analysis" => Ok(BodyPhase::Analysis),
+            "optimized" => Ok(BodyPhase::Optimized),
+            _ => Err(format!(
+                "unknown MIR phase {}, expected built, analysis or optimized",
+                s
+            )),
+        }
+    }
+}
+
+impl fmt::Display for BodyPhase {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(match self {
+            BodyPhase::Built => "built",
+            BodyPhase::Analysis => "analysis",
+            BodyPhase::Optimized => "optimized",
+        })
+    }
+}
+
+thread_local! {
+    /// Addresses of the arena copies of the bodies of the early phases, for the
+    /// compiler session running on this thread. The query providers are plain `fn`s, so
+    /// the bodies can't be kept in the callback. They only ever point into the arena of
+    /// the running session: `RtoolCallback::config` forgets those of earlier sessions,
+    /// and `after_analysis`, the only reader, forgets them when it returns or unwinds.
+    static KEPT_BODIES: RefCell<FxHashMap<(BodyPhase, LocalDefId), usize>> =
+        RefCell::new(FxHashMap::default());
+}
+
+fn keep_body<'tcx>(tcx: TyCtxt<'tcx>, phase: BodyPhase, def: LocalDefId, body: &Steal<Body<'tcx>>) {
+    let body: &'tcx Body<'tcx> = tcx.arena.alloc(body.borrow().clone());
+    KEPT_BODIES.with_borrow_mut(|kept| kept.insert((phase, def), body as *const Body as usize));
+}
+
+fn kept_body<'tcx>(
+    _tcx: TyCtxt<'tcx>,
+    phase: BodyPhase,
+    def: LocalDefId,
+) -> Option<&'tcx Body<'tcx>> {
+    let addr = KEPT_BODIES.with_borrow(|kept| kept.get(&(phase, def)).copied())?;
+    // SAFETY: the body was allocated in the arena of the session running on this
+    // thread, which `_tcx` belongs to, and is forgotten before the arena is dropped, see
+    // `KEPT_BODIES`.
+    Some(unsafe { &*(addr as *const Body<'tcx>) })
+}
+
+/// Forget the bodies kept for the sessions that ran on this thread.
+pub fn forget_kept_bodies() {
+    KEPT_BODIES.with_borrow_mut(|kept| kept.clear());
+}
+
+/// Forgets the kept bodies when dropped, so that a panicking analysis does too.
+pub struct ForgetKeptBodies;
+
+impl Drop for ForgetKeptBodies {
+    fn drop(&mut self) {
+        forget_kept_bodies();
+    }
+}
+
+/// Override the queries of the early phases to keep a copy of each body.
+pub fn keep_early_phases(providers: &mut Providers) {
+    providers.queries.mir_built = |tcx, def| {
+        let mut providers = Providers::default();
+        rustc_mir_transform::provide(&mut providers);
+        let body = (providers.queries.mir_built)(tcx, def);
+        keep_body(tcx, BodyPhase::Built, def, body);
+        body
+    };
+    providers.queries.mir_drops_elaborated_and_const_checked = |tcx, def| {
+        let mut providers = Providers::default();
+        rustc_mir_transform::provide(&mut providers);
+        let body = (providers.queries.mir_drops_elaborated_and_const_checked)(tcx, def);
+        keep_body(tcx, BodyPhase::Analysis, def, body);
+        body
+    };
+}
+
+/// Fetch the MIR of a body owner in `phase`, or the reason it can't be shown.
+pub fn fetch_mir_in_phase<'tcx>(
+    tcx: TyCtxt<'tcx>,
+    def_id: DefId,
+    phase: BodyPhase,
+) -> Result<&'tcx Body<'tcx>, String> {
+    if phase == BodyPhase::Optimized {
+        return fetch_mir(tcx, def_id);
+    }
+    let Some(def) = def_id.as_local() else {
+        return Err(format!(
+            "the {} MIR of other crates is not available",
+            phase
+        ));
+    };
+    let body =
+        kept_body(tcx, phase, def).ok_or_else(|| format!("the {} MIR is not available", phase))?;
+    if body.tainted_by_errors.is_some() {
+        return Err("the body has errors".to_string());
+    }
+    Ok(body)
+}
@@ -0,0 +1,142 @@
+//! The `-mirstats` output: one line of counts per function instead of its full MIR.
+
+use std::io::Write;
+
+use rustc_hir::def_id::{DefId, LOCAL_CRATE};
+use rustc_middle::mir::{Body, TerminatorKind};
+use rustc_middle::ty::TyCtxt;
+
+use super::{MirOutput, fetch_mir, report_skipped, warn_if_errors};
+use crate::{rtool_error, rtool_info};
+
+const CSV_HEADER: &str = "crate,fn,blocks,statements,calls,drops,cleanup_blocks,locals\n";
+
+#[derive(Debug, Default, Clone, Copy)]
+struct MirStats {
+    blocks: usize,
+    statements: usize,
+    calls: usize,
+    drops: usize,
+    cleanup_blocks: usize,
+    locals: usize,
+}
+
+impl MirStats {
+    fn of(body: &Body) -> Self {
+        let blocks = &body.basic_blocks;
+        let terminators = || blocks.iter().filter_map(|data| data.terminator.as_ref());
+        Self {
+            blocks: blocks.len(),
+            statements: blocks.iter().map(|data| data.statements.len()).sum(),
+            calls: terminators()
+                .filter(|term| {
+                    matches!(
+                        term.kind,
+                        TerminatorKind::Call { .. } | TerminatorKind::TailCall { .. }
+                    )
+                })
+                .count(),
+            drops: terminators()
+                .filter(|term| matches!(term.kind, TerminatorKind::Drop { .. }))
+                .count(),
+            cleanup_blocks: blocks.iter().filter(|data| data.is_cleanup).count(),
+            locals: body.local_decls.len(),
+        }
+    }
+
+    fn add(&mut self, other: &MirStats) {
+        self.blocks += other.blocks;
+        self.statements += other.statements;
+        self.calls += other.calls;
+        self.drops += other.drops;
+        self.cleanup_blocks += other.cleanup_blocks;
+        self.locals += other.locals;
+    }
+
+    fn line(&self, name: &str) -> String {
+        format!(
+            "{} blocks={} statements={} calls={} drops={} cleanup_blocks={} locals={}\n",
+            name,
+            self.blocks,
+            self.statements,
+            self.calls,
+            self.drops,
+            self.cleanup_blocks,
+            self.locals
+        )
+    }
+
+    fn csv_line(&self, crate_name: &str, name: &str) -> String {
+        format!(
+            "{},{},{},{},{},{},{},{}\n",
+            csv_field(crate_name),
+            csv_field(name),
+            self.blocks,
+            self.statements,
+            self.calls,
+            self.drops,
+            self.cleanup_blocks,
+            self.locals
+        )
+    }
+}
+
+/// Quote a field containing commas or quotes, such as the def path of a generic impl.
+fn csv_field(field: &str) -> String {
+    if field.contains([',', '"', '\n']) {
+        format!("\"{}\"", field.replace('"', "\"\""))
+    } else {
+        field.to_string()
+    }
+}
+
+pub struct ShowMirStats<'tcx> {
+    tcx: TyCtxt<'tcx>,
+    output: MirOutput,
+    /// Write CSV rows without the totals, rather than lines ending with the totals.
+    csv: bool,
+}
+
+impl<'tcx> ShowMirStats<'tcx> {
+    pub fn new(tcx: TyCtxt<'tcx>, output: MirOutput, csv: bool) -> Self {
+        Self { tcx, output, csv }
+    }
+
+    pub fn start(&self) {
+        rtool_info!("Show MIR stats");
+        warn_if_errors(self.tcx);
+        let mut stats: Vec<(String, MirStats)> = vec![];
+        for each_mir in self.tcx.mir_keys(()) {
+            let def_id: DefId = each_mir.to_def_id();
+            match fetch_mir(self.tcx, def_id) {
+                Ok(body) => stats.push((self.tcx.def_path_str(def_id), MirStats::of(body))),
+                Err(e) => report_skipped(self.tcx, def_id, &e),
+            }
+        }
+        // Biggest first, ties by path so that the output is reproducible.
+        stats.sort_by(|a, b| b.1.blocks.cmp(&a.1.blocks).then_with(|| a.0.cmp(&b.0)));
+
+        let crate_name = self.tcx.crate_name(LOCAL_CRATE);
+        let mut out: Vec<u8> = vec![];
+        let mut total = MirStats::default();
+        for (name, each) in stats.iter() {
+            let line = if self.csv {
+                each.csv_line(crate_name.as_str(), name)
+            } else {
+                each.line(name)
+            };
+            if let Err(e) = out.write_all(line.as_bytes()) {
+                rtool_error!("{}", e.to_string());
+            }
+            total.add(each);
+        }
+        if !self.csv {
+            let name = format!("total of {} fns in {}:", stats.len(), crate_name);
+            if let Err(e) = out.write_all(total.line(&name).as_bytes()) {
+                rtool_error!("{}", e.to_string());
+            }
+        }
+        self.output
+            .write(if self.csv { CSV_HEADER } else { "" }, &out);
+    }
+}
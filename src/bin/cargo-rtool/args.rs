@@ -1,3 +1,6 @@
+use crate::config;
+use rtool::RtoolCallback;
+use rtool::options::{self, OptionScope};
 use std::{
     env,
     path::{Path, PathBuf},
@@ -54,7 +57,11 @@ impl Arguments {
         let args: Vec<_> = env::args().collect();
         let path = env::current_exe().expect("Current executable path invalid.");
         rtool_trace!("Current exe: {path:?}\tReceived args: {args:?}");
-        let [args_group1, args_group2] = split_args_by_double_dash(&args);
+        let [cli_args, args_group2] = split_args_by_double_dash(&args);
+        // The defaults of rtool.toml come first, so that a value given twice is
+        // overridden by the command line.
+        let mut args_group1 = config::get().rtool_args(&cli_args);
+        args_group1.extend(cli_args);
 
         Arguments {
             args,
@@ -106,24 +113,142 @@ pub fn rtool_and_cargo_args() -> [&'static [String]; 2] {
     [&ARGS.args_group1, &ARGS.args_group2]
 }
 
+/// Check the rtool options against `options::RTOOL_OPTIONS` before running cargo,
+/// rather than failing in the middle of the check. The options are applied to the
+/// returned callback, for cargo-rtool to know what every crate will be asked for.
+pub fn check_rtool_args() -> Result<RtoolCallback, String> {
+    let mut callback = RtoolCallback::default();
+    let mut args = ARGS.args_group1.iter();
+    while let Some(arg) = args.next() {
+        let Some(option) = options::find_option(arg) else {
+            return Err(match options::suggest_option(arg) {
+                Some(name) => format!("Unknown option {}, did you mean {}?", arg, name),
+                None => format!("Unknown option {}, see `cargo rtool -help`.", arg),
+            });
+        };
+        if option.scope == OptionScope::CargoRtool {
+            return Err(format!("{} must be the only option.", arg));
+        }
+        if option.scope == OptionScope::Binary {
+            return Err(format!("{} is only supported by the rtool binary.", arg));
+        }
+        if let Some(value) = option.value {
+            let Some(value) = args.next() else {
+                return Err(format!("{} requires {}.", arg, value));
+            };
+            if option.scope == OptionScope::Members {
+                continue;
+            }
+            callback
+                .set_option_value(arg, value.clone())
+                .map_err(|e| format!("Invalid {}: {}", arg, e))?;
+        } else {
+            callback.enable_option(arg)?;
+        }
+    }
+    Ok(callback)
+}
+
+/// The path given to cargo rtool for `option`, resolved against the current folder.
+///
+/// cargo runs rustc in a different folder for each workspace member, so a relative
+/// path can't be forwarded as is.
+fn absolute_path_arg(option: &str) -> Option<PathBuf> {
+    let pos = ARGS.args_group1.iter().position(|arg| arg == option)?;
+    let path = ARGS.args_group1.get(pos + 1)?;
+    Some(std::path::absolute(path).unwrap_or_else(|_| PathBuf::from(path)))
+}
+
+/// The `-outpath` given to cargo rtool, made absolute.
+pub fn outpath() -> Option<PathBuf> {
+    absolute_path_arg("-outpath")
+}
+
+/// If the MIR is written as a `dumpfmt` dump, rather than as JSON, DOT, Markdown,
+/// a list or stats.
+pub fn plain_mir_dump() -> bool {
+    let mut args = ARGS.args_group1.iter();
+    while let Some(arg) = args.next() {
+        match arg.as_str() {
+            "-mirjson" | "-mirdot" | "-mirlist" | "-mirstats" | "-mirstatscsv" => return false,
+            "-mirformat" if args.next().is_some_and(|format| format != "plain") => return false,
+            _ => {}
+        }
+    }
+    true
+}
+
+/// The package names given to cargo rtool with `option`, i.e. `-only` or `-exclude`.
+pub fn member_names(option: &str) -> Vec<&'static str> {
+    let mut names = vec![];
+    let mut args = ARGS.args_group1.iter();
+    while let Some(arg) = args.next() {
+        if arg == option
+            && let Some(name) = args.next()
+        {
+            names.push(name.as_str());
+        }
+    }
+    names
+}
+
+/// Rtool options to forward to the rtool binary, with `-outpath` and `-outdir`
+/// made absolute, and without the options picking workspace members.
+pub fn rtool_args() -> Vec<String> {
+    let mut rtool_args = vec![];
+    let mut args = ARGS.args_group1.iter();
+    while let Some(arg) = args.next() {
+        match options::find_option(arg) {
+            Some(option) if option.scope == OptionScope::Members => {
+                args.next();
+            }
+            _ => rtool_args.push(arg.clone()),
+        }
+    }
+    for option in ["-outpath", "-outdir"] {
+        if let Some(path) = absolute_path_arg(option)
+            && let Some(pos) = rtool_args.iter().position(|arg| arg == option)
+            && let Some(arg) = rtool_args.get_mut(pos + 1)
+        {
+            *arg = path.to_string_lossy().into_owned();
+        }
+    }
+    rtool_args
+}
+
 /// If a crate being compiled is local in rustc phase.
 pub fn is_current_compile_crate() -> bool {
     ARGS.is_current_compile_crate()
 }
 
-/// Returns true for crate types to be checked;
-/// returns false for some special crate types that can't be handled by rtool.
-/// For example, checking proc-macro crates or build.rs can cause linking errors in rtool.
+/// The kind of the crate being compiled in rustc phase, as named in
+/// `RTOOL_ANALYZE_CRATE_TYPES`: the `--crate-type` value, `custom-build` for build
+/// scripts, or `test`.
+pub fn crate_kind() -> &'static str {
+    match get_arg_flag_value("--crate-type") {
+        Some("bin") if get_arg_flag_value("--crate-name") == Some("build_script_build") => {
+            "custom-build"
+        }
+        Some(crate_type) => crate_type,
+        // NOTE: tests don't have --crate-type, they are handled with --test by rustc.
+        None => "test",
+    }
+}
+
+/// Returns true for crate types to be checked.
+/// By default, proc-macro crates and build scripts are skipped since they run on the
+/// host at build time rather than being part of the checked program.
+///
+/// `RTOOL_ANALYZE_CRATE_TYPES` replaces the default with a comma-separated list of
+/// crate kinds to check, see `crate_kind`.
 pub fn filter_crate_type() -> bool {
-    if let Some(s) = get_arg_flag_value("--crate-type") {
-        return match s {
-            "proc-macro" => false,
-            "bin" if get_arg_flag_value("--crate-name") == Some("build_script_build") => false,
-            _ => true,
-        };
+    let kind = crate_kind();
+    match env::var("RTOOL_ANALYZE_CRATE_TYPES") {
+        Ok(kinds) => kinds
+            .split(',')
+            .any(|k| k.trim().eq_ignore_ascii_case(kind)),
+        Err(_) => !matches!(kind, "proc-macro" | "custom-build"),
     }
-    // NOTE: tests don't have --crate-type, they are handled with --test by rustc.
-    true
 }
 
 pub fn get_arg(pos: usize) -> Option<&'static str> {
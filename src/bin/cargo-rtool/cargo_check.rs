@@ -1,24 +1,73 @@
 use crate::args;
-use cargo_metadata::camino::Utf8Path;
-use rtool::utils::log::rtool_error_and_exit;
-use std::{env, process::Command, time::Duration};
+use cargo_metadata::camino::{Utf8Path, Utf8PathBuf};
+use rtool::RtoolCallback;
+use rtool::utils::{
+    dumpfmt,
+    fs::{rtool_check_output_file, rtool_create_file},
+    log::rtool_error_and_exit,
+};
+use std::{
+    env, fs,
+    io::{self, BufRead, BufReader, Read, Write},
+    path::{Path, PathBuf},
+    process::{self, Child, Command, ExitStatus, Stdio},
+    sync::{Mutex, OnceLock},
+    thread,
+    time::Duration,
+};
 use wait_timeout::ChildExt;
 
 mod workspace;
 
-pub fn run() {
+/// The file each crate appends the -mir/-mirexact/-mirregex/-mirat targets it matched
+/// to, so that the targets matched by no crate are reported once, see
+/// `RtoolCallback::set_matched_targets_file`.
+static MATCHED_TARGETS: OnceLock<PathBuf> = OnceLock::new();
+
+/// The manifest the crates appending to a plain dump share to skip the functions
+/// another crate already dumped, see `dumpfmt::claim_section`.
+static DUMP_MANIFEST: OnceLock<PathBuf> = OnceLock::new();
+
+pub fn run(callback: &RtoolCallback) {
+    if let Some(path) = args::outpath() {
+        prepare_outpath(&path);
+    }
+    let targets = callback.find_mir_targets();
+    if !targets.is_empty() {
+        let path = env::temp_dir().join(format!("rtool-matched-targets-{}", process::id()));
+        rtool_create_file(&path, "Failed to create the matched targets file");
+        MATCHED_TARGETS.get_or_init(|| path);
+    }
+
     match env::var("RTOOL_RECURSIVE")
         .ok()
         .map(|s| s.trim().to_ascii_lowercase())
         .as_deref()
     {
-        Some("none") | None => default_run(),
+        Some("none") | None => {
+            if args::member_names("-only").len() + args::member_names("-exclude").len() > 0 {
+                rtool_warn!("-only and -exclude only apply to shallow or deep RTOOL_RECURSIVE");
+            }
+            default_run()
+        }
         Some("deep") => workspace::deep_run(),
         Some("shallow") => workspace::shallow_run(),
         _ => rtool_error_and_exit(
             "`recursive` should only accept one the values: none, shallow or deep.",
         ),
     }
+
+    if let Some(path) = args::outpath()
+        && args::plain_mir_dump()
+    {
+        report_cross_references(&path);
+    }
+    if let Some(path) = DUMP_MANIFEST.get() {
+        let _ = fs::remove_file(path);
+    }
+    if let Some(path) = MATCHED_TARGETS.get() {
+        report_unmatched_targets(path, &targets, callback.is_strict_match_enabled());
+    }
 }
 
 fn cargo_check(dir: &Utf8Path) {
@@ -26,8 +75,40 @@ fn cargo_check(dir: &Utf8Path) {
     rtool_trace!("cargo clean in package folder {dir}");
     cargo_clean(dir, args::rtool_clean());
 
+    let child = cargo_check_command(dir)
+        .spawn()
+        .expect("Could not run cargo check.");
+    match wait_for(child) {
+        Some(status) => {
+            // cargo has printed why, e.g. an unknown --target or feature.
+            if !status.success() {
+                rtool_error!("cargo check failed with {}.", status);
+                fail(status.code().unwrap_or(1));
+            }
+        }
+        None => {
+            rtool_error!("Process killed due to timeout.");
+            fail(1);
+        }
+    }
+}
+
+/// Exit with `code` after a failed check, removing the files the crates of the run
+/// shared.
+fn fail(code: i32) -> ! {
+    for path in [DUMP_MANIFEST.get(), MATCHED_TARGETS.get()]
+        .into_iter()
+        .flatten()
+    {
+        let _ = fs::remove_file(path);
+    }
+    process::exit(code)
+}
+
+fn cargo_check_command(dir: &Utf8Path) -> Command {
     rtool_trace!("cargo check in package folder {dir}");
-    let [rtool_args, cargo_args] = args::rtool_and_cargo_args();
+    let [_, cargo_args] = args::rtool_and_cargo_args();
+    let rtool_args = args::rtool_args();
     rtool_trace!("rtool_args={rtool_args:?}\tcargo_args={cargo_args:?}");
 
     /*Here we prepare the cargo command as cargo check, which is similar to build, but much faster*/
@@ -35,7 +116,9 @@ fn cargo_check(dir: &Utf8Path) {
     cmd.current_dir(dir);
     cmd.arg("check");
 
-    /* set the target as a filter for phase_rustc_rtool */
+    // Forwarded verbatim, e.g. --features, --no-default-features or --target. cargo
+    // passes the --target on to the rustc of each local crate, so the wrapper hands it
+    // to rtool along with the rest of the rustc arguments.
     cmd.args(cargo_args);
 
     // Serialize the remaining args into a special environment variable.
@@ -44,38 +127,185 @@ fn cargo_check(dir: &Utf8Path) {
 
     cmd.env(
         "rtool_ARGS",
-        serde_json::to_string(rtool_args).expect("Failed to serialize args."),
+        serde_json::to_string(&rtool_args).expect("Failed to serialize args."),
     );
+    // Every local crate appends its output to the file prepared by `prepare_outpath`.
+    cmd.env("RTOOL_OUTPATH_APPEND", "true");
+    if let Some(path) = MATCHED_TARGETS.get() {
+        cmd.env("RTOOL_MATCHED_TARGETS", path);
+    }
+    if let Some(path) = DUMP_MANIFEST.get() {
+        cmd.env("RTOOL_DUMP_MANIFEST", path);
+    }
 
     // Invoke actual cargo for the job, but with different flags.
     let cargo_rtool_path = args::current_exe_path();
     cmd.env("RUSTC_WRAPPER", cargo_rtool_path);
 
     rtool_trace!("Command is: {:?}.", cmd);
+    cmd
+}
 
-    let mut child = cmd.spawn().expect("Could not run cargo check.");
+/// Wait for a `cargo check`, or kill it and return `None` after an hour.
+fn wait_for(mut child: Child) -> Option<ExitStatus> {
     match child
         .wait_timeout(Duration::from_secs(60 * 60)) // 1 hour timeout
         .expect("Failed to wait for subprocess.")
     {
-        Some(status) => {
-            if !status.success() {
-                rtool_error_and_exit("Finished with non-zero exit code.");
-            }
-        }
+        Some(status) => Some(status),
         None => {
             child.kill().expect("Failed to kill subprocess.");
             child.wait().expect("Failed to wait for subprocess.");
-            rtool_error_and_exit("Process killed due to timeout.");
+            None
         }
+    }
+}
+
+/// The number of members checked at the same time, from `RTOOL_JOBS`.
+fn jobs() -> usize {
+    match env::var("RTOOL_JOBS") {
+        Ok(jobs) => match jobs.trim().parse() {
+            Ok(jobs) if jobs > 0 => jobs,
+            _ => rtool_error_and_exit("`RTOOL_JOBS` should be a positive number."),
+        },
+        Err(_) => thread::available_parallelism().map_or(1, |n| n.get()),
+    }
+}
+
+/// A workspace member to check.
+struct Member {
+    name: String,
+    dir: Utf8PathBuf,
+    /// The members of a workspace get their own target folder, since cargo locks the
+    /// shared one and would check them one at a time.
+    target_dir: Utf8PathBuf,
+}
+
+/// Run `cargo check` for the given members, up to `RTOOL_JOBS` at a time, and exit
+/// with the status of the first member that failed, if any. The folders aren't
+/// cleaned here, see `workspace::check_workspaces`.
+fn check_members(members: Vec<Member>) {
+    let queue = Mutex::new(members.iter().enumerate());
+    let failed = Mutex::new(Vec::new());
+    thread::scope(|scope| {
+        for _ in 0..jobs().min(members.len()) {
+            scope.spawn(|| {
+                loop {
+                    // Not in a `while let`, which would hold the lock while checking.
+                    let Some((idx, member)) = queue.lock().unwrap().next() else {
+                        break;
+                    };
+                    let status = check_member(member);
+                    if !status.is_some_and(|status| status.success()) {
+                        failed.lock().unwrap().push((idx, &member.name, status));
+                    }
+                }
+            });
+        }
+    });
+
+    let mut failed = failed.into_inner().unwrap();
+    failed.sort_by_key(|(idx, ..)| *idx);
+    for (_, name, status) in &failed {
+        match status {
+            Some(status) => rtool_error!("cargo check failed in {} with {}.", name, status),
+            None => rtool_error!("cargo check killed in {} due to timeout.", name),
+        }
+    }
+    if let Some((_, _, status)) = failed.first() {
+        fail(status.and_then(|status| status.code()).unwrap_or(1));
+    }
+}
+
+/// Check one member, prefixing the lines it prints and logs with `[name]` to tell the
+/// members running at the same time apart.
+fn check_member(member: &Member) -> Option<ExitStatus> {
+    let name = &member.name;
+    let mut child = cargo_check_command(&member.dir)
+        .env("CARGO_TARGET_DIR", &member.target_dir)
+        .stdout(Stdio::piped())
+        .stderr(Stdio::piped())
+        .spawn()
+        .expect("Could not run cargo check.");
+    let stdout = child.stdout.take().expect("Failed to capture stdout.");
+    let stderr = child.stderr.take().expect("Failed to capture stderr.");
+    thread::scope(|scope| {
+        scope.spawn(|| prefix_lines(name, stdout, io::stdout()));
+        scope.spawn(|| prefix_lines(name, stderr, io::stderr()));
+        wait_for(child)
+    })
+}
+
+/// Copy the lines of `input` to `out`, each prefixed with `[name]`.
+fn prefix_lines(name: &str, input: impl Read, mut out: impl Write) {
+    for line in BufReader::new(input).lines().map_while(Result::ok) {
+        let _ = writeln!(out, "[{name}] {line}");
+    }
+}
+
+/// Validate and truncate the output file once, before any crate is compiled,
+/// so that errors show up early and stale output from previous runs is dropped.
+/// The dump header is written here since each crate only appends its sections, along
+/// with the `DUMP_MANIFEST` of a plain dump.
+fn prepare_outpath(path: &Path) {
+    if let Err(e) = rtool_check_output_file(path) {
+        rtool_error_and_exit(format!("Invalid output path: {}", e));
+    }
+    let mut file = rtool_create_file(path, "Failed to create the output file");
+    if !args::plain_mir_dump() {
+        return;
+    }
+    if let Err(e) = dumpfmt::write_header(&mut file) {
+        rtool_error_and_exit(format!("Failed to write the output file: {}", e));
+    }
+    // The crates create it once they dump a function.
+    let manifest = env::temp_dir().join(format!("rtool-dump-manifest-{}", process::id()));
+    DUMP_MANIFEST.get_or_init(|| manifest);
+}
+
+/// Summarize the functions dumped by one crate and referred to by others.
+fn report_cross_references(path: &Path) {
+    let Ok(dump) = fs::read_to_string(path) else {
+        return;
     };
+    match dumpfmt::split_sections(&dump) {
+        Ok(sections) => {
+            let refs = sections
+                .iter()
+                .filter(|section| section.dumped_in.is_some())
+                .count();
+            if refs > 0 {
+                rtool_info!(
+                    "Avoided {} duplicate MIR dumps, see the ref lines in {}",
+                    refs,
+                    path.display()
+                );
+            }
+        }
+        Err(e) => rtool_warn!("Failed to read back {}: {}", path.display(), e),
+    }
+}
+
+/// Warn about the targets no crate matched, and fail with -strict-match.
+fn report_unmatched_targets(path: &Path, targets: &[String], strict: bool) {
+    let matched = fs::read_to_string(path).unwrap_or_default();
+    let _ = fs::remove_file(path);
+    let matched: Vec<&str> = matched.lines().collect();
+    let unmatched: Vec<&String> = targets
+        .iter()
+        .filter(|target| !matched.contains(&target.as_str()))
+        .collect();
+    for target in &unmatched {
+        rtool_warn!("No fn matched {}", target);
+    }
+    if strict && !unmatched.is_empty() {
+        rtool_error_and_exit("Some targets matched no fn, see -strict-match");
+    }
 }
 
 fn cargo_clean(dir: &Utf8Path, really: bool) {
-    if really {
-        if let Err(err) = Command::new("cargo").arg("clean").current_dir(dir).output() {
-            rtool_error_and_exit(format!("`cargo clean` exits unexpectedly:\n{err}"));
-        }
+    if really && let Err(err) = Command::new("cargo").arg("clean").current_dir(dir).output() {
+        rtool_error_and_exit(format!("`cargo clean` exits unexpectedly:\n{err}"));
     }
 }
 
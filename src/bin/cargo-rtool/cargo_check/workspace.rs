@@ -1,9 +1,11 @@
+use super::Member;
+use crate::args;
 use cargo_metadata::{
     Metadata,
     camino::{Utf8Path, Utf8PathBuf},
 };
 use rtool::utils::log::rtool_error_and_exit;
-use std::collections::BTreeMap;
+use std::collections::{BTreeMap, BTreeSet};
 
 /// Run cargo check in each member folder under current workspace.
 pub fn shallow_run() {
@@ -12,33 +14,68 @@ pub fn shallow_run() {
         rtool_error_and_exit("rtool should be run in a folder directly containing Cargo.toml");
     }
     let ws_metadata = workspace(cargo_toml);
-    check_members(&ws_metadata);
+    check_workspaces([&ws_metadata]);
 }
 
 /// Recursively run cargo check in each package folder from current folder.
 pub fn deep_run() {
     let cargo_tomls = get_cargo_tomls_deep_recursively(".");
-    for ws_metadata in workspaces(&cargo_tomls).values() {
-        check_members(ws_metadata);
+    check_workspaces(workspaces(&cargo_tomls).values());
+}
+
+/// Check the members of the workspaces picked by `-only` and `-exclude`, and warn
+/// about the names these options give that match no member.
+fn check_workspaces<'a>(workspaces: impl IntoIterator<Item = &'a Metadata>) {
+    let only = args::member_names("-only");
+    let exclude = args::member_names("-exclude");
+    let mut matched: BTreeSet<&str> = BTreeSet::new();
+    let mut members = vec![];
+    for ws_metadata in workspaces {
+        let mut picked = get_members(ws_metadata);
+        for member in &picked {
+            matched.extend(
+                only.iter()
+                    .chain(&exclude)
+                    .filter(|given| **given == member.name),
+            );
+        }
+        picked.retain(|member| {
+            let name = member.name.as_str();
+            (only.is_empty() || only.contains(&name)) && !exclude.contains(&name)
+        });
+        if picked.is_empty() {
+            continue;
+        }
+        // Members aren't cleaned one by one, since their target folders are all in
+        // the one of the workspace.
+        clean_workspace(ws_metadata);
+        members.extend(picked);
     }
+
+    for (option, names) in [("-only", &only), ("-exclude", &exclude)] {
+        for name in names.iter().filter(|name| !matched.contains(*name)) {
+            rtool_warn!("{} {} matched no workspace member", option, name);
+        }
+    }
+    super::check_members(members);
 }
 
-fn check_members(ws_metadata: &Metadata) {
+fn clean_workspace(ws_metadata: &Metadata) {
     // Force clean even if `RTOOL_CLEAN` is false, because rtool is in control of
     // caches for all packages and there should be no cache.
     let ws_root = &ws_metadata.workspace_root;
     rtool_trace!("cargo clean in workspace root {ws_root}");
     super::cargo_clean(ws_root, true);
-
-    for pkg_folder in get_member_folders(ws_metadata) {
-        super::cargo_check(pkg_folder);
-    }
 }
 
-fn get_member_folders(meta: &Metadata) -> Vec<&Utf8Path> {
+fn get_members(meta: &Metadata) -> Vec<Member> {
     meta.workspace_packages()
         .iter()
-        .map(|pkg| pkg.manifest_path.parent().unwrap())
+        .map(|pkg| Member {
+            name: pkg.name.to_string(),
+            dir: pkg.manifest_path.parent().unwrap().to_path_buf(),
+            target_dir: meta.target_directory.join("rtool").join(pkg.name.as_str()),
+        })
         .collect()
 }
 
@@ -79,11 +116,12 @@ fn get_cargo_tomls_deep_recursively(dir: &str) -> Vec<Utf8PathBuf> {
     walkdir::WalkDir::new(dir)
         .into_iter()
         .filter_map(|entry| {
-            if let Ok(e) = entry {
-                if e.file_type().is_file() && e.file_name().to_str()? == "Cargo.toml" {
-                    let path = Utf8PathBuf::from_path_buf(e.into_path());
-                    return path.ok()?.canonicalize_utf8().ok();
-                }
+            if let Ok(e) = entry
+                && e.file_type().is_file()
+                && e.file_name().to_str()? == "Cargo.toml"
+            {
+                let path = Utf8PathBuf::from_path_buf(e.into_path());
+                return path.ok()?.canonicalize_utf8().ok();
             }
             None
         })
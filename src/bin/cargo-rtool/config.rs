@@ -0,0 +1,121 @@
+use serde::Deserialize;
+use std::{
+    env, fs,
+    path::{Path, PathBuf},
+    process::Command,
+    sync::OnceLock,
+};
+
+/// The config file read from the workspace root, see `options::CONFIG_HELP`.
+pub const CONFIG_FILE: &str = "rtool.toml";
+
+/// Defaults for the options of `cargo rtool`, read from `rtool.toml`. Options given
+/// on the command line, and environment variables already set, take precedence.
+#[derive(Debug, Default, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct Config {
+    /// `-allmir`
+    pub allmir: bool,
+    /// `-mir` targets.
+    pub mir: Vec<String>,
+    /// `-mirexact` targets.
+    pub mirexact: Vec<String>,
+    /// `-outpath`, relative to the folder of the file.
+    pub outpath: Option<PathBuf>,
+    /// `RTOOL_RECURSIVE`
+    pub recursive: Option<String>,
+    /// `RTOOL_LOG`
+    pub log: Option<String>,
+}
+
+static CONFIG: OnceLock<Config> = OnceLock::new();
+
+/// The config loaded by `load`, or the built-in defaults.
+pub fn get() -> &'static Config {
+    CONFIG.get_or_init(Config::default)
+}
+
+/// Read `rtool.toml` from the workspace root, if there is one, and export its
+/// `recursive` and `log` settings unless the environment sets them already.
+///
+/// Called before the logger is initialized, since the file may set its level.
+pub fn load() -> Result<(), String> {
+    let Some(path) = workspace_root().map(|root| root.join(CONFIG_FILE)) else {
+        return Ok(());
+    };
+    let config = match fs::read_to_string(&path) {
+        Ok(text) => parse(&text, path.parent().unwrap_or(Path::new(".")))
+            .map_err(|e| format!("Invalid {}: {}", path.display(), e))?,
+        Err(_) => Config::default(),
+    };
+    config.export_env();
+    let _ = CONFIG.set(config);
+    Ok(())
+}
+
+fn parse(text: &str, dir: &Path) -> Result<Config, String> {
+    let mut config: Config = toml::from_str(text).map_err(|e| e.message().to_string())?;
+    config.outpath = config.outpath.map(|path| dir.join(path));
+    Ok(config)
+}
+
+/// The folder of the workspace containing the current folder, as cargo finds it.
+fn workspace_root() -> Option<PathBuf> {
+    let output = Command::new("cargo")
+        .args(["locate-project", "--workspace", "--message-format", "plain"])
+        .output()
+        .ok()?;
+    if !output.status.success() {
+        return None;
+    }
+    let manifest = PathBuf::from(String::from_utf8_lossy(&output.stdout).trim());
+    manifest.parent().map(Path::to_path_buf)
+}
+
+impl Config {
+    /// The rtool options of the file, unless `cli` gives the same kind of option: any
+    /// target on the command line replaces the targets of the file.
+    pub fn rtool_args(&self, cli: &[String]) -> Vec<String> {
+        let given = |names: &[&str]| cli.iter().any(|arg| names.contains(&arg.as_str()));
+        let mut args = vec![];
+        if self.allmir && !given(&["-allmir"]) {
+            args.push("-allmir".to_string());
+        }
+        if !given(&[
+            "-mir",
+            "-mirexact",
+            "-mirdefid",
+            "-mirregex",
+            "-mirat",
+            "-mirlist",
+            "-mirconsts",
+        ]) {
+            for target in &self.mir {
+                args.extend(["-mir".to_string(), target.clone()]);
+            }
+            for target in &self.mirexact {
+                args.extend(["-mirexact".to_string(), target.clone()]);
+            }
+        }
+        if let Some(ref path) = self.outpath
+            && !given(&["-outpath", "-outdir"])
+        {
+            args.extend(["-outpath".to_string(), path.to_string_lossy().into_owned()]);
+        }
+        args
+    }
+
+    fn export_env(&self) {
+        for (name, value) in [
+            ("RTOOL_RECURSIVE", &self.recursive),
+            ("RTOOL_LOG", &self.log),
+        ] {
+            if let Some(value) = value
+                && env::var_os(name).is_none()
+            {
+                // SAFETY: cargo-rtool hasn't started any other thread yet.
+                unsafe { env::set_var(name, value) };
+            }
+        }
+    }
+}
@@ -7,9 +7,11 @@
 #[macro_use]
 extern crate rtool;
 
+use rtool::options;
 use rtool::utils::log::{init_log, rtool_error_and_exit};
 
 mod args;
+mod config;
 mod help;
 
 mod utils;
@@ -31,13 +33,18 @@ fn phase_cargo_rtool() {
             return;
         }
         "-help" => {
-            rtool_info!("{}", help::RTOOL_HELP);
+            rtool_info!("{}", options::help_text());
+            return;
+        }
+        "-help-markdown" => {
+            println!("{}", options::help_markdown());
             return;
         }
         _ => {}
     }
 
-    cargo_check::run();
+    let callback = args::check_rtool_args().unwrap_or_else(|e| rtool_error_and_exit(e));
+    cargo_check::run(&callback);
 }
 
 fn phase_rustc_wrapper() {
@@ -45,9 +52,19 @@ fn phase_rustc_wrapper() {
 
     let is_direct = args::is_current_compile_crate();
     // rtool only checks local crates
-    if is_direct && args::filter_crate_type() {
-        run_rtool();
-        return;
+    if is_direct {
+        let crate_name = args::get_arg_flag_value("--crate-name").unwrap_or("<unknown>");
+        let crate_kind = args::crate_kind();
+        if args::filter_crate_type() {
+            rtool_info!("Analyze crate {} ({})", crate_name, crate_kind);
+            run_rtool();
+            return;
+        }
+        rtool_info!(
+            "Skip crate {} ({}), see RTOOL_ANALYZE_CRATE_TYPES",
+            crate_name,
+            crate_kind
+        );
     }
 
     // for dependencies and some special crate types, run rustc as usual
@@ -62,8 +79,22 @@ fn main() {
           Because RUSTC_WRAPPER is defined, Cargo calls the command: `$RUSTC_WRAPPER path/rustc ...`
     */
 
+    // rtool.toml may set the log level, so it is read first; its errors are reported
+    // once the logger is up.
+    let is_cargo_rtool = std::env::args()
+        .nth(1)
+        .is_some_and(|arg| arg.ends_with("rtool"));
+    let config = if is_cargo_rtool {
+        config::load()
+    } else {
+        Ok(())
+    };
+
     // Init the log_system
     init_log().expect("Failed to init log.");
+    if let Err(e) = config {
+        rtool_error_and_exit(e);
+    }
 
     match args::get_arg(1).unwrap() {
         s if s.ends_with("rtool") => phase_cargo_rtool(),
@@ -1,6 +1,8 @@
 use crate::args;
 use std::{
     env,
+    fs::OpenOptions,
+    io::Write,
     path::PathBuf,
     process::{self, Command},
 };
@@ -23,13 +25,33 @@ pub fn run_cmd(mut cmd: Command) {
     }
 }
 
+/// Record which compiler handles the current crate into the file named by
+/// `RTOOL_WRAPPER_LOG`, one `<compiler> <crate name>` line per invocation.
+fn log_wrapper_choice(compiler: &str) {
+    let Some(path) = env::var_os("RTOOL_WRAPPER_LOG") else {
+        return;
+    };
+    let crate_name = args::get_arg_flag_value("--crate-name").unwrap_or("<unknown>");
+    let line = format!("{compiler} {crate_name}\n");
+    let res = OpenOptions::new()
+        .append(true)
+        .create(true)
+        .open(&path)
+        .and_then(|mut file| file.write_all(line.as_bytes()));
+    if let Err(err) = res {
+        rtool_warn!("Failed to write RTOOL_WRAPPER_LOG {:?}: {}", path, err);
+    }
+}
+
 pub fn run_rustc() {
+    log_wrapper_choice("rustc");
     let mut cmd = Command::new("rustc");
     cmd.args(args::skip2());
     run_cmd(cmd);
 }
 
 pub fn run_rtool() {
+    log_wrapper_choice("rtool");
     let mut cmd = Command::new(find_rtool());
     cmd.args(args::skip2());
     let magic = env::var("rtool_ARGS").expect("Missing rtool_ARGS.");
@@ -4,7 +4,10 @@ extern crate rustc_driver;
 extern crate rustc_session;
 
 use rtool::{
-    RTOOL_DEFAULT_ARGS, RtoolCallback, rtool_error, rtool_info, rtool_trace, utils::log::init_log,
+    RTOOL_DEFAULT_ARGS, RtoolCallback, findings,
+    options::{OptionScope, RtoolOption, find_option, suggest_option},
+    rtool_info, rtool_trace, rtool_warn, unmatched_targets,
+    utils::log::{init_log, rtool_error_and_exit},
 };
 use rustc_session::EarlyDiagCtxt;
 use rustc_session::config::ErrorOutputType;
@@ -24,9 +27,7 @@ fn run_complier(args: &mut Vec<String>, callback: &mut RtoolCallback) {
 
 enum ArgParserState {
     Ready,
-    MirName,
-    MirNameExact,
-    OutPath,
+    Value(&'static RtoolOption),
 }
 
 fn main() {
@@ -37,43 +38,75 @@ fn main() {
     let mut state = ArgParserState::Ready;
     for arg in env::args() {
         match state {
-            ArgParserState::Ready => match arg.as_str() {
-                "-allmir" => compiler.enable_show_all_mir(),
-                "-lockdev" => compiler.enable_lockdev(),
-                "-mir" => state = ArgParserState::MirName,
-                "-mirexact" => state = ArgParserState::MirNameExact,
-                "-outpath" => state = ArgParserState::OutPath,
-                _ => args.push(arg),
-            },
-            ArgParserState::MirName => {
-                if arg.starts_with("-") {
-                    rtool_error!("Invalid function name: {}", arg);
-                    return;
+            ArgParserState::Ready => match find_option(&arg) {
+                Some(option)
+                    if matches!(option.scope, OptionScope::Rtool | OptionScope::Binary) =>
+                {
+                    match option.value {
+                        Some(_) => state = ArgParserState::Value(option),
+                        None => {
+                            if let Err(e) = compiler.enable_option(option.name) {
+                                rtool_error_and_exit(e);
+                            }
+                        }
+                    }
                 }
-                compiler.enable_show_mir_fuzzy(arg);
-                state = ArgParserState::Ready;
-            }
-            ArgParserState::MirNameExact => {
-                if arg.starts_with("-") {
-                    rtool_error!("Invalid function name: {}", arg);
-                    return;
+                _ => {
+                    // Other arguments go to rustc, which rejects the unknown ones.
+                    if let Some(name) = suggest_option(&arg) {
+                        rtool_warn!("Unknown option {}, did you mean {}?", arg, name);
+                    }
+                    args.push(arg)
                 }
-                compiler.enable_show_mir_exact(arg);
-                state = ArgParserState::Ready;
-            }
-            ArgParserState::OutPath => {
+            },
+            ArgParserState::Value(option) => {
                 if arg.starts_with("-") {
-                    rtool_error!("Invalid output path: {}", arg);
-                    return;
+                    rtool_error_and_exit(format!(
+                        "Invalid {} for {}: {}",
+                        option.value.unwrap(),
+                        option.name,
+                        arg
+                    ));
+                }
+                if let Err(e) = compiler.set_option_value(option.name, arg) {
+                    rtool_error_and_exit(format!("Invalid {}: {}", option.name, e));
                 }
-                compiler.set_mir_output_file(arg);
                 state = ArgParserState::Ready;
             }
         }
     }
+    if let ArgParserState::Value(option) = state {
+        rtool_error_and_exit(format!(
+            "{} requires {}.",
+            option.name,
+            option.value.unwrap()
+        ));
+    }
+    // Set by cargo-rtool, which prepares the output file once for all the crates it compiles.
+    if env::var_os("RTOOL_OUTPATH_APPEND").is_some() {
+        compiler.enable_mir_output_append();
+    }
+    // Set by cargo-rtool along with RTOOL_OUTPATH_APPEND for a plain dump.
+    if let Ok(path) = env::var("RTOOL_DUMP_MANIFEST") {
+        compiler.set_dump_manifest(path);
+    }
+    // Set by cargo-rtool, which reports the targets matched by no crate once at the end.
+    if let Ok(path) = env::var("RTOOL_MATCHED_TARGETS") {
+        compiler.set_matched_targets_file(path);
+    }
     rtool_info!("Start analysis with Rtool.");
     rtool_trace!("rtool received arguments{:#?}", env::args());
     rtool_trace!("arguments to rustc: {:?}", &args);
 
     run_complier(&mut args, &mut compiler);
+    if compiler.is_strict_match_enabled() && !unmatched_targets().is_empty() {
+        rtool_error_and_exit("Some targets matched no fn, see -strict-match");
+    }
+    // cargo-rtool fails the cargo check, and so the run, with this crate.
+    if findings() > 0 && !compiler.is_exit_zero_enabled() {
+        rtool_error_and_exit(format!(
+            "{} potential bugs reported, see -exit-zero",
+            findings()
+        ));
+    }
 }
@@ -3,6 +3,7 @@
 #![feature(macro_metavar_expr_concat)]
 
 pub mod analysis;
+pub mod options;
 pub mod utils;
 extern crate rustc_abi;
 extern crate rustc_ast;
@@ -15,12 +16,14 @@ extern crate rustc_index;
 extern crate rustc_interface;
 extern crate rustc_metadata;
 extern crate rustc_middle;
+extern crate rustc_mir_transform;
 extern crate rustc_public;
 extern crate rustc_session;
 extern crate rustc_span;
 extern crate rustc_target;
 extern crate thin_vec;
 
+use regex::Regex;
 use rustc_ast::ast;
 use rustc_driver::{Callbacks, Compilation};
 use rustc_interface::{
@@ -28,27 +31,99 @@ use rustc_interface::{
     interface::{self, Compiler},
 };
 use rustc_middle::{ty::TyCtxt, util::Providers};
+use rustc_session::Session;
 use rustc_session::search_paths::PathKind;
 use std::path::PathBuf;
-use std::sync::Arc;
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::{Arc, Mutex, PoisonError};
 
-use analysis::show_mir::ShowAllMir;
+use analysis::show_mir::{ShowAllMir, ShowMirStats};
 
-use crate::analysis::{dev::LockDevTool, show_mir::FindAndShowMir};
+use crate::analysis::{
+    dev::LockDevTool,
+    serve::MirServer,
+    show_mir::{
+        BodyPhase, ColorChoice, FindAndShowMir, ForgetKeptBodies, MirCase, MirFormat, MirOutput,
+        forget_kept_bodies, keep_early_phases, parse_mir_def_id, parse_mir_location,
+        split_fn_names, target_names,
+    },
+};
+use crate::utils::fs::{rtool_append_lines, rtool_check_output_dir, rtool_check_output_file};
 
 // Insert rustc arguments at the beginning of the argument list that rtool wants to be
 // set per default, for maximal validation power.
 pub static RTOOL_DEFAULT_ARGS: &[&str] = &["-Zalways-encode-mir", "-Zmir-opt-level=0"];
 
+/// An analysis registered by a library user through `RtoolCallback::register_analysis`.
+#[derive(Clone)]
+struct UserAnalysis {
+    name: String,
+    run: Arc<dyn for<'tcx> Fn(TyCtxt<'tcx>) + Send + Sync>,
+}
+
+impl std::fmt::Debug for UserAnalysis {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("UserAnalysis")
+            .field("name", &self.name)
+            .finish_non_exhaustive()
+    }
+}
+
+impl std::hash::Hash for UserAnalysis {
+    fn hash<H: std::hash::Hasher>(&self, state: &mut H) {
+        self.name.hash(state);
+    }
+}
+
+static FINDINGS: AtomicUsize = AtomicUsize::new(0);
+
+/// Report a potential bug found by an analysis, as a warning. The rtool binary exits
+/// with an error once the crate is compiled if any was reported, unless `-exit-zero`
+/// is given.
+pub fn report_finding(finding: impl std::fmt::Display) {
+    rtool_warn!("{}", finding);
+    FINDINGS.fetch_add(1, Ordering::SeqCst);
+}
+
+/// The number of potential bugs reported with `report_finding` in this process.
+pub fn findings() -> usize {
+    FINDINGS.load(Ordering::SeqCst)
+}
+
+static UNMATCHED_TARGETS: Mutex<Vec<String>> = Mutex::new(vec![]);
+
+/// The targets which matched no fn of the crates analyzed by this process, see
+/// `RtoolCallback::find_mir_targets`.
+pub fn unmatched_targets() -> Vec<String> {
+    UNMATCHED_TARGETS
+        .lock()
+        .unwrap_or_else(PoisonError::into_inner)
+        .clone()
+}
+
 /// This is the data structure to handle rtool options as a rustc callback.
 
 #[derive(Debug, Clone, Hash)]
 pub struct RtoolCallback {
     show_all_mir: bool,
+    show_all_mir_filters: Vec<String>,
+    show_all_mir_consts: bool,
+    show_mir_stats: bool,
+    mir_stats_csv: bool,
     lockdev: bool,
     show_mir_list: Vec<String>,
     show_mir_fuzzy_list: Vec<String>,
-    show_mir_output_file: Option<String>,
+    show_mir_fuzzy_case: MirCase,
+    show_mir_regex_list: Vec<String>,
+    show_mir_at_list: Vec<(String, usize)>,
+    show_mir_def_id_list: Vec<(u32, u32)>,
+    show_mir_consts: bool,
+    show_mir_output: MirOutput,
+    serve: bool,
+    strict_match: bool,
+    exit_zero: bool,
+    matched_targets_file: Option<String>,
+    user_analyses: Vec<UserAnalysis>,
 }
 
 #[allow(clippy::derivable_impls)]
@@ -56,27 +131,57 @@ impl Default for RtoolCallback {
     fn default() -> Self {
         Self {
             show_all_mir: false,
+            show_all_mir_filters: vec![],
+            show_all_mir_consts: false,
+            show_mir_stats: false,
+            mir_stats_csv: false,
             lockdev: false,
             show_mir_list: vec![],
             show_mir_fuzzy_list: vec![],
-            show_mir_output_file: None,
+            show_mir_fuzzy_case: MirCase::default(),
+            show_mir_regex_list: vec![],
+            show_mir_at_list: vec![],
+            show_mir_def_id_list: vec![],
+            show_mir_consts: false,
+            show_mir_output: MirOutput::default(),
+            serve: false,
+            strict_match: false,
+            exit_zero: false,
+            matched_targets_file: None,
+            user_analyses: vec![],
         }
     }
 }
 
+fn override_queries(_: &Session, providers: &mut Providers) {
+    providers.extern_queries.used_crate_source = |tcx, cnum| {
+        let mut providers = Providers::default();
+        rustc_metadata::provide(&mut providers);
+        let mut crate_source = (providers.extern_queries.used_crate_source)(tcx, cnum);
+        // HACK: rustc will emit "crate ... required to be available in rlib format, but
+        // was not found in this form" errors once we use `tcx.dependency_formats()` if
+        // there's no rlib provided, so setting a dummy path here to workaround those errors.
+        // Real rlibs are kept, so that crates which are linked (build scripts and
+        // proc-macros) can still be analyzed.
+        if crate_source.rlib.is_none() {
+            Arc::make_mut(&mut crate_source).rlib = Some((PathBuf::new(), PathKind::All));
+        }
+        crate_source
+    };
+}
+
+/// For `-mirphase built|analysis`, whose bodies rustc would steal before rtool runs.
+fn override_queries_keeping_phases(session: &Session, providers: &mut Providers) {
+    override_queries(session, providers);
+    keep_early_phases(providers);
+}
+
 impl Callbacks for RtoolCallback {
     fn config(&mut self, config: &mut Config) {
-        config.override_queries = Some(|_, providers| {
-            providers.extern_queries.used_crate_source = |tcx, cnum| {
-                let mut providers = Providers::default();
-                rustc_metadata::provide(&mut providers);
-                let mut crate_source = (providers.extern_queries.used_crate_source)(tcx, cnum);
-                // HACK: rustc will emit "crate ... required to be available in rlib format, but
-                // was not found in this form" errors once we use `tcx.dependency_formats()` if
-                // there's no rlib provided, so setting a dummy path here to workaround those errors.
-                Arc::make_mut(&mut crate_source).rlib = Some((PathBuf::new(), PathKind::All));
-                crate_source
-            };
+        forget_kept_bodies();
+        config.override_queries = Some(match self.show_mir_output.phase {
+            BodyPhase::Optimized => override_queries,
+            BodyPhase::Built | BodyPhase::Analysis => override_queries_keeping_phases,
         });
     }
     fn after_crate_root_parsing(
@@ -88,6 +193,7 @@ impl Callbacks for RtoolCallback {
     }
     fn after_analysis<'tcx>(&mut self, _compiler: &Compiler, tcx: TyCtxt<'tcx>) -> Compilation {
         rtool_trace!("Execute after_analysis() of compiler callbacks");
+        let _forget = ForgetKeptBodies;
         rustc_public::rustc_internal::run(tcx, || {
             start_analyzer(tcx, self.clone());
         })
@@ -98,6 +204,72 @@ impl Callbacks for RtoolCallback {
 }
 
 impl RtoolCallback {
+    /// Apply a switch from `options::RTOOL_OPTIONS`.
+    pub fn enable_option(&mut self, name: &str) -> Result<(), String> {
+        match name {
+            "-allmir" => self.enable_show_all_mir(),
+            "-allmir-include-consts" => self.enable_show_all_mir_consts(),
+            "-mirconsts" => self.enable_show_mir_consts(),
+            "-mirstats" => self.enable_show_mir_stats(),
+            "-mirstatscsv" => self.enable_show_mir_stats_csv(),
+            "-nocolor" => self.set_color(ColorChoice::Never),
+            "-lockdev" => self.enable_lockdev(),
+            "-no-dedup" => self.disable_mir_dedup(),
+            "-mirjson" => self.set_mir_format(MirFormat::Json),
+            "-mirdot" => self.set_mir_format(MirFormat::Dot),
+            "-mirpromoted" => self.enable_show_mir_promoted(),
+            "-mirspans" => self.enable_show_mir_all_spans(),
+            "-mirsource" => self.enable_show_mir_source(),
+            "-mircfg" => self.enable_show_mir_cfg(),
+            "-mirinstances" => self.enable_show_mir_instances(),
+            "-serve" => self.enable_serve(),
+            "-strict-match" => self.enable_strict_match(),
+            "-exit-zero" => self.enable_exit_zero(),
+            _ => return Err(format!("{} is not an rtool switch", name)),
+        }
+        Ok(())
+    }
+
+    /// Apply an option taking a value from `options::RTOOL_OPTIONS`.
+    pub fn set_option_value(&mut self, name: &str, value: String) -> Result<(), String> {
+        match name {
+            "-allmir-filter" => self.add_show_all_mir_filter(value),
+            "-mir" => split_fn_names(&value)?
+                .into_iter()
+                .for_each(|name| self.enable_show_mir_fuzzy(name)),
+            "-mirexact" => split_fn_names(&value)?
+                .into_iter()
+                .for_each(|name| self.enable_show_mir_exact(name)),
+            "-mirdefid" => self.enable_show_mir_def_id(&value)?,
+            "-mirregex" => self.enable_show_mir_regex(value)?,
+            "-mirat" => self.enable_show_mir_at(&value)?,
+            "-mircase" => self.set_show_mir_fuzzy_case(value.parse()?),
+            "-mirphase" => self.set_mir_phase(value.parse()?),
+            "-color" => self.set_color(value.parse()?),
+            "-mirformat" => self.set_mir_format(value.parse()?),
+            "-mirlist" => {
+                self.enable_show_mir_fuzzy(value);
+                self.set_mir_format(MirFormat::List);
+            }
+            "-outpath" => {
+                if self.show_mir_output.dir.is_some() {
+                    return Err("-outpath can't be combined with -outdir".to_string());
+                }
+                rtool_check_output_file(&value)?;
+                self.set_mir_output_file(value);
+            }
+            "-outdir" => {
+                if self.show_mir_output.file.is_some() {
+                    return Err("-outdir can't be combined with -outpath".to_string());
+                }
+                rtool_check_output_dir(&value)?;
+                self.set_mir_output_dir(value);
+            }
+            _ => return Err(format!("{} is not an rtool option taking a value", name)),
+        }
+        Ok(())
+    }
+
     /// Enable mir display.
     pub fn enable_show_all_mir(&mut self) {
         self.show_all_mir = true;
@@ -108,6 +280,36 @@ impl RtoolCallback {
         self.show_all_mir
     }
 
+    /// Limit -allmir to the bodies under a module prefix; may be called several times.
+    pub fn add_show_all_mir_filter(&mut self, prefix: String) {
+        self.show_all_mir_filters.push(prefix);
+    }
+
+    /// Also show the initializers of consts and statics with -allmir.
+    pub fn enable_show_all_mir_consts(&mut self) {
+        self.show_all_mir_consts = true;
+    }
+
+    /// Show per-function MIR counts instead of the MIR itself.
+    pub fn enable_show_mir_stats(&mut self) {
+        self.show_mir_stats = true;
+    }
+
+    /// Show per-function MIR counts as CSV.
+    pub fn enable_show_mir_stats_csv(&mut self) {
+        self.show_mir_stats = true;
+        self.mir_stats_csv = true;
+    }
+
+    pub fn is_show_mir_stats_enabled(&self) -> bool {
+        self.show_mir_stats
+    }
+
+    /// Color the plain MIR of -allmir and -mir/-mirexact, see `ColorChoice`.
+    pub fn set_color(&mut self, color: ColorChoice) {
+        self.show_mir_output.color = color;
+    }
+
     pub fn enable_lockdev(&mut self) {
         self.lockdev = true;
     }
@@ -124,19 +326,202 @@ impl RtoolCallback {
         self.show_mir_fuzzy_list.push(fn_name);
     }
 
+    /// Compare -mir/-mirlist targets ignoring case or not, see `MirCase`.
+    pub fn set_show_mir_fuzzy_case(&mut self, case: MirCase) {
+        self.show_mir_fuzzy_case = case;
+    }
+
+    /// Match def paths against `pattern`, which must be a valid regex.
+    pub fn enable_show_mir_regex(&mut self, pattern: String) -> Result<(), String> {
+        Regex::new(&pattern).map_err(|e| format!("invalid regex {}: {}", pattern, e))?;
+        self.show_mir_regex_list.push(pattern);
+        Ok(())
+    }
+
+    /// Show the body of a `<crate>:<index>` `DefId`.
+    pub fn enable_show_mir_def_id(&mut self, def_id: &str) -> Result<(), String> {
+        self.show_mir_def_id_list.push(parse_mir_def_id(def_id)?);
+        Ok(())
+    }
+
+    /// Show the bodies spanning a `<file>:<line>` location.
+    pub fn enable_show_mir_at(&mut self, location: &str) -> Result<(), String> {
+        self.show_mir_at_list.push(parse_mir_location(location)?);
+        Ok(())
+    }
+
+    /// Show the initializers of every const and static.
+    pub fn enable_show_mir_consts(&mut self) {
+        self.show_mir_consts = true;
+    }
+
     pub fn is_find_mir_enabled(&self) -> bool {
-        !self.show_mir_list.is_empty() || !self.show_mir_fuzzy_list.is_empty()
+        !self.show_mir_list.is_empty()
+            || !self.show_mir_fuzzy_list.is_empty()
+            || !self.show_mir_regex_list.is_empty()
+            || !self.show_mir_at_list.is_empty()
+            || !self.show_mir_def_id_list.is_empty()
+            || self.show_mir_consts
+    }
+
+    /// The -mir/-mirexact/-mirdefid/-mirregex/-mirat targets, named by
+    /// `show_mir::target_names`.
+    pub fn find_mir_targets(&self) -> Vec<String> {
+        target_names(
+            &self.show_mir_list,
+            &self.show_mir_fuzzy_list,
+            &self.show_mir_def_id_list,
+            &self.show_mir_regex_list,
+            &self.show_mir_at_list,
+        )
+    }
+
+    /// Fail the run if a target matches no fn, see `unmatched_targets`.
+    pub fn enable_strict_match(&mut self) {
+        self.strict_match = true;
+    }
+
+    pub fn is_strict_match_enabled(&self) -> bool {
+        self.strict_match
+    }
+
+    /// Succeed even if an analysis reported a potential bug, see `report_finding`.
+    pub fn enable_exit_zero(&mut self) {
+        self.exit_zero = true;
+    }
+
+    pub fn is_exit_zero_enabled(&self) -> bool {
+        self.exit_zero
+    }
+
+    /// Record the targets matching a fn in `path` instead of warning about the others.
+    /// cargo-rtool sets it to report the targets no crate of a run matched, once.
+    pub fn set_matched_targets_file(&mut self, path: String) {
+        self.matched_targets_file = Some(path);
+    }
+
+    fn report_unmatched_targets(&self, matched: &[String]) {
+        if let Some(ref path) = self.matched_targets_file {
+            if let Err(e) = rtool_append_lines(path, matched) {
+                rtool_error!("Failed to record the matched targets in {}: {}", path, e);
+            }
+            return;
+        }
+        for target in self.find_mir_targets() {
+            if !matched.contains(&target) {
+                rtool_warn!("No fn matched {}", target);
+                UNMATCHED_TARGETS
+                    .lock()
+                    .unwrap_or_else(PoisonError::into_inner)
+                    .push(target);
+            }
+        }
     }
 
     pub fn set_mir_output_file(&mut self, filename: String) {
-        self.show_mir_output_file = Some(filename);
+        self.show_mir_output.file = Some(filename);
+    }
+
+    /// Write each function to its own file in `dir`, see `show_mir::mir_file_name`.
+    pub fn set_mir_output_dir(&mut self, dir: String) {
+        self.show_mir_output.dir = Some(dir);
+    }
+
+    /// Append to the mir output file instead of truncating it.
+    pub fn enable_mir_output_append(&mut self) {
+        self.show_mir_output.append = true;
+    }
+
+    /// Record the functions dumped in this manifest, shared by the crates of a run.
+    pub fn set_dump_manifest(&mut self, path: String) {
+        self.show_mir_output.manifest = Some(path);
+    }
+
+    pub fn set_mir_format(&mut self, format: MirFormat) {
+        self.show_mir_output.format = format;
+    }
+
+    /// Also show the bodies promoted out of the matched functions.
+    pub fn enable_show_mir_promoted(&mut self) {
+        self.show_mir_output.promoted = true;
+    }
+
+    /// Show the span of every statement instead of one per basic block.
+    pub fn enable_show_mir_all_spans(&mut self) {
+        self.show_mir_output.all_spans = true;
+    }
+
+    /// Start each basic block with the source lines it comes from.
+    pub fn enable_show_mir_source(&mut self) {
+        self.show_mir_output.source = true;
+    }
+
+    /// Follow the basic blocks with their immediate dominators and loop headers.
+    pub fn enable_show_mir_cfg(&mut self) {
+        self.show_mir_output.cfg = true;
+    }
+
+    /// Follow each matched generic function with the bodies of its instances.
+    pub fn enable_show_mir_instances(&mut self) {
+        self.show_mir_output.instances = true;
+    }
+
+    /// Show the bodies of the matched functions in `phase` rather than optimized.
+    pub fn set_mir_phase(&mut self, phase: BodyPhase) {
+        self.show_mir_output.phase = phase;
+    }
+
+    /// Dump functions shared by several crates of a cargo-rtool run once per crate.
+    pub fn disable_mir_dedup(&mut self) {
+        self.show_mir_output.dedup = false;
+    }
+
+    /// Answer requests on stdin after the analysis instead of exiting.
+    pub fn enable_serve(&mut self) {
+        self.serve = true;
+    }
+
+    pub fn is_serve_enabled(&self) -> bool {
+        self.serve
+    }
+
+    /// Register a custom analysis to run after the built-in ones.
+    ///
+    /// The analysis is called once per compiled crate from `after_analysis`, inside
+    /// `rustc_public::rustc_internal::run`. The `TyCtxt` it receives only lives for the
+    /// duration of that call, so nothing borrowed from it (bodies, types, interned
+    /// symbols) may be stored beyond it; copy out owned data such as strings instead.
+    pub fn register_analysis(
+        &mut self,
+        name: &str,
+        f: Box<dyn for<'tcx> Fn(TyCtxt<'tcx>) + Send + Sync>,
+    ) {
+        self.user_analyses.push(UserAnalysis {
+            name: name.to_string(),
+            run: Arc::from(f),
+        });
     }
 }
 
 /// Start the analysis with the features enabled.
 pub fn start_analyzer(tcx: TyCtxt, callback: RtoolCallback) {
     if callback.is_show_all_mir_enabled() {
-        ShowAllMir::new(tcx).start();
+        ShowAllMir::new(
+            tcx,
+            callback.show_mir_output.color,
+            &callback.show_all_mir_filters,
+            callback.show_all_mir_consts,
+        )
+        .start();
+    }
+
+    if callback.is_show_mir_stats_enabled() {
+        ShowMirStats::new(
+            tcx,
+            callback.show_mir_output.clone(),
+            callback.mir_stats_csv,
+        )
+        .start();
     }
 
     if callback.is_lockdev_enabled() {
@@ -144,12 +529,28 @@ pub fn start_analyzer(tcx: TyCtxt, callback: RtoolCallback) {
     }
 
     if callback.is_find_mir_enabled() {
-        FindAndShowMir::new(
+        let matched = FindAndShowMir::new(
             tcx,
             &callback.show_mir_list,
             &callback.show_mir_fuzzy_list,
-            callback.show_mir_output_file,
+            callback.show_mir_fuzzy_case,
+            &callback.show_mir_def_id_list,
+            &callback.show_mir_regex_list,
+            &callback.show_mir_at_list,
+            callback.show_mir_consts,
+            callback.show_mir_output.clone(),
         )
         .start();
+        callback.report_unmatched_targets(&matched);
+    }
+
+    for analysis in callback.user_analyses.iter() {
+        rtool_info!("Run analysis {}", analysis.name);
+        (analysis.run)(tcx);
+    }
+
+    // Serve last, so that requests see the results of everything above.
+    if callback.is_serve_enabled() {
+        MirServer::new(tcx).start();
     }
 }
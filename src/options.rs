@@ -0,0 +1,506 @@
+//! The rtool options, defined once for the argument parsers of both binaries and for
+//! the help text.
+
+/// Which binary consumes an option.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum OptionScope {
+    /// Parsed by the rtool binary; cargo-rtool forwards it.
+    Rtool,
+    /// Handled by cargo-rtool itself, as the first argument of `cargo rtool`.
+    CargoRtool,
+    /// Picks the workspace members cargo-rtool checks; not forwarded.
+    Members,
+    /// Parsed by the rtool binary; cargo-rtool rejects it, as it would reach every crate.
+    Binary,
+}
+
+/// The section of the help text an option is listed in.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum OptionGroup {
+    Analysis,
+    Output,
+    Workspace,
+    General,
+}
+
+impl OptionGroup {
+    fn title(&self) -> &'static str {
+        match self {
+            OptionGroup::Analysis => "Analysis",
+            OptionGroup::Output => "Output",
+            OptionGroup::Workspace => "Workspace",
+            OptionGroup::General => "General command",
+        }
+    }
+}
+
+#[derive(Debug)]
+pub struct RtoolOption {
+    pub name: &'static str,
+    /// Placeholder of the value following the option, or None for a switch.
+    pub value: Option<&'static str>,
+    pub help: &'static str,
+    pub scope: OptionScope,
+    pub group: OptionGroup,
+}
+
+#[derive(Debug)]
+pub struct EnvVar {
+    pub name: &'static str,
+    /// May span several lines.
+    pub help: &'static str,
+}
+
+pub static RTOOL_OPTIONS: &[RtoolOption] = &[
+    RtoolOption {
+        name: "-allmir",
+        value: None,
+        help: "show mir of every fn",
+        scope: OptionScope::Rtool,
+        group: OptionGroup::Analysis,
+    },
+    RtoolOption {
+        name: "-allmir-filter",
+        value: Some("module::prefix"),
+        help: "with -allmir, only show fns whose def path or module starts with\n\
+               module::prefix; may be repeated",
+        scope: OptionScope::Rtool,
+        group: OptionGroup::Analysis,
+    },
+    RtoolOption {
+        name: "-allmir-include-consts",
+        value: None,
+        help: "with -allmir, also show the initializers of consts and statics",
+        scope: OptionScope::Rtool,
+        group: OptionGroup::Analysis,
+    },
+    RtoolOption {
+        name: "-mirstats",
+        value: None,
+        help: "show the block, statement, call, drop, cleanup block and local counts\n\
+               of every fn, biggest first, then the totals; honors -outpath",
+        scope: OptionScope::Rtool,
+        group: OptionGroup::Analysis,
+    },
+    RtoolOption {
+        name: "-mirstatscsv",
+        value: None,
+        help: "like -mirstats, as CSV rows with a header row and no totals",
+        scope: OptionScope::Rtool,
+        group: OptionGroup::Analysis,
+    },
+    RtoolOption {
+        name: "-mir",
+        value: Some("fn_name"),
+        help: "show mir of fns whose name contains fn_name; a comma-separated list\n\
+               like a,b,c gives several, with \\, for a comma in a name",
+        scope: OptionScope::Rtool,
+        group: OptionGroup::Analysis,
+    },
+    RtoolOption {
+        name: "-mircase",
+        value: Some("case"),
+        help: "with insensitive, -mir and -mirlist ignore case, e.g. irqhandler\n\
+               matches IrqHandler and IRQHandler; sensitive is the default",
+        scope: OptionScope::Rtool,
+        group: OptionGroup::Analysis,
+    },
+    RtoolOption {
+        name: "-mirexact",
+        value: Some("fn_name"),
+        help: "show mir with def_path_str = fn_name; takes a list like -mir, where\n\
+               the commas of generic args, as in HashMap::<K, V>::get, need no \\",
+        scope: OptionScope::Rtool,
+        group: OptionGroup::Analysis,
+    },
+    RtoolOption {
+        name: "-mirdefid",
+        value: Some("crate:index"),
+        help: "show mir of the fn with this DefId, e.g. 0:5 for DefId(0:5 ~ krate::f),\n\
+               to tell apart fns sharing a def_path_str",
+        scope: OptionScope::Rtool,
+        group: OptionGroup::Analysis,
+    },
+    RtoolOption {
+        name: "-mirconsts",
+        value: None,
+        help: "show mir of the initializers of every const and static, labeled with\n\
+               their kind and type",
+        scope: OptionScope::Rtool,
+        group: OptionGroup::Analysis,
+    },
+    RtoolOption {
+        name: "-mirregex",
+        value: Some("pattern"),
+        help: "show mir of fns whose def_path_str matches the regex pattern",
+        scope: OptionScope::Rtool,
+        group: OptionGroup::Analysis,
+    },
+    RtoolOption {
+        name: "-mirphase",
+        value: Some("phase"),
+        help: "show the mir of -mir/-mirexact as built, as analyzed after drop\n\
+               elaboration, or optimized (the default): built|analysis|optimized",
+        scope: OptionScope::Rtool,
+        group: OptionGroup::Analysis,
+    },
+    RtoolOption {
+        name: "-mirat",
+        value: Some("file:line"),
+        help: "show mir of the bodies spanning line of file, closures included;\n\
+               file is absolute or the end of a path, such as src/sched.rs",
+        scope: OptionScope::Rtool,
+        group: OptionGroup::Analysis,
+    },
+    RtoolOption {
+        name: "-mirlist",
+        value: Some("pattern"),
+        help: "like -mir pattern, but list each fn as one line with its block count\n\
+               and location instead of its mir, then the number of fns",
+        scope: OptionScope::Rtool,
+        group: OptionGroup::Analysis,
+    },
+    RtoolOption {
+        name: "-strict-match",
+        value: None,
+        help: "exit with an error after compiling if a -mir, -mirexact, -mirdefid,\n\
+               -mirregex or -mirat target matches no fn; such targets are always\n\
+               warned about",
+        scope: OptionScope::Rtool,
+        group: OptionGroup::Analysis,
+    },
+    RtoolOption {
+        name: "-exit-zero",
+        value: None,
+        help: "exit successfully even if an analysis reported a potential bug",
+        scope: OptionScope::Rtool,
+        group: OptionGroup::Analysis,
+    },
+    RtoolOption {
+        name: "-lockdev",
+        value: None,
+        help: "list the callers of interrupt_enable APIs",
+        scope: OptionScope::Rtool,
+        group: OptionGroup::Analysis,
+    },
+    RtoolOption {
+        name: "-outpath",
+        value: Some("path"),
+        help: "write the output of -mir/-mirexact to path instead of stdout",
+        scope: OptionScope::Rtool,
+        group: OptionGroup::Output,
+    },
+    RtoolOption {
+        name: "-outdir",
+        value: Some("dir"),
+        help: "write each fn of -mir/-mirexact to its own file in dir,\n\
+               named <crate>.<def path>.txt",
+        scope: OptionScope::Rtool,
+        group: OptionGroup::Output,
+    },
+    RtoolOption {
+        name: "-mirformat",
+        value: Some("format"),
+        help: "write the output of -mir/-mirexact as plain|json|dot|md; md writes a\n\
+               section per fn with its locals as a table and its blocks as code",
+        scope: OptionScope::Rtool,
+        group: OptionGroup::Output,
+    },
+    RtoolOption {
+        name: "-mirjson",
+        value: None,
+        help: "the same as -mirformat json: one JSON object per fn and line",
+        scope: OptionScope::Rtool,
+        group: OptionGroup::Output,
+    },
+    RtoolOption {
+        name: "-mirdot",
+        value: None,
+        help: "the same as -mirformat dot: one Graphviz digraph of the basic blocks\n\
+               per fn, cleanup blocks dashed",
+        scope: OptionScope::Rtool,
+        group: OptionGroup::Output,
+    },
+    RtoolOption {
+        name: "-mirpromoted",
+        value: None,
+        help: "with -mir/-mirexact, also show the bodies promoted out of each fn,\n\
+               under promoted[N] lines (plain output only)",
+        scope: OptionScope::Rtool,
+        group: OptionGroup::Output,
+    },
+    RtoolOption {
+        name: "-mirspans",
+        value: None,
+        help: "with -mir/-mirexact, show the span of every statement and terminator\n\
+               instead of one per basic block (plain output only)",
+        scope: OptionScope::Rtool,
+        group: OptionGroup::Output,
+    },
+    RtoolOption {
+        name: "-mirsource",
+        value: None,
+        help: "with -mir/-mirexact, start each basic block with the source lines it\n\
+               comes from as // comments (plain output only)",
+        scope: OptionScope::Rtool,
+        group: OptionGroup::Output,
+    },
+    RtoolOption {
+        name: "-mirinstances",
+        value: None,
+        help: "with -mir/-mirexact, follow each generic fn with the body of each of\n\
+               its instances found in the crate, named with their generic args",
+        scope: OptionScope::Rtool,
+        group: OptionGroup::Output,
+    },
+    RtoolOption {
+        name: "-mircfg",
+        value: None,
+        help: "with -mir/-mirexact, follow the basic blocks with the immediate\n\
+               dominator of each block, marking loop headers (plain output only)",
+        scope: OptionScope::Rtool,
+        group: OptionGroup::Output,
+    },
+    RtoolOption {
+        name: "-no-dedup",
+        value: None,
+        help: "with cargo rtool -outpath, dump a function again for every crate\n\
+               matching it, instead of a ref line to the crate which dumped it",
+        scope: OptionScope::Rtool,
+        group: OptionGroup::Output,
+    },
+    RtoolOption {
+        name: "-serve",
+        value: None,
+        help: "after the analysis, answer JSON-RPC requests on stdin (one per line),\n\
+               e.g. {\"id\":1,\"method\":\"dumpMir\",\"params\":{\"def_path\":\"foo\"}};\n\
+               only supported by the rtool binary",
+        scope: OptionScope::Binary,
+        group: OptionGroup::Output,
+    },
+    RtoolOption {
+        name: "-color",
+        value: Some("when"),
+        help: "color the mir of -allmir and -mir/-mirexact: always|never|auto;\n\
+               auto (the default) colors it on a terminal only, and files are only\n\
+               colored with always",
+        scope: OptionScope::Rtool,
+        group: OptionGroup::Output,
+    },
+    RtoolOption {
+        name: "-nocolor",
+        value: None,
+        help: "the same as -color never",
+        scope: OptionScope::Rtool,
+        group: OptionGroup::Output,
+    },
+    RtoolOption {
+        name: "-only",
+        value: Some("crate"),
+        help: "with shallow or deep RTOOL_RECURSIVE, only check the workspace\n\
+               member of this package name; may be repeated",
+        scope: OptionScope::Members,
+        group: OptionGroup::Workspace,
+    },
+    RtoolOption {
+        name: "-exclude",
+        value: Some("crate"),
+        help: "with shallow or deep RTOOL_RECURSIVE, skip the workspace member\n\
+               of this package name; may be repeated",
+        scope: OptionScope::Members,
+        group: OptionGroup::Workspace,
+    },
+    RtoolOption {
+        name: "-help",
+        value: None,
+        help: "show help information",
+        scope: OptionScope::CargoRtool,
+        group: OptionGroup::General,
+    },
+    RtoolOption {
+        name: "-help-markdown",
+        value: None,
+        help: "show help information as markdown",
+        scope: OptionScope::CargoRtool,
+        group: OptionGroup::General,
+    },
+    RtoolOption {
+        name: "-version",
+        value: None,
+        help: "show the version and commit of rtool, and the rustc it is built with",
+        scope: OptionScope::CargoRtool,
+        group: OptionGroup::General,
+    },
+];
+
+pub static RTOOL_ENV_VARS: &[EnvVar] = &[
+    EnvVar {
+        name: "RTOOL_LOG",
+        help: "verbosity of logging: trace, debug, info, warn\n\
+               trace: print all the detailed rtool execution traces.\n\
+               debug: display intermidiate analysis results.\n\
+               warn: show bugs detected only.",
+    },
+    EnvVar {
+        name: "RTOOL_CLEAN",
+        help: "run cargo clean before check: true, false\n\
+               * true is the default value except that false is set",
+    },
+    EnvVar {
+        name: "RTOOL_RECURSIVE",
+        help: "scope of packages to check: none, shallow, deep\n\
+               * none or the variable not set: check for current folder\n\
+               * shallow: check for current workpace members\n\
+               * deep: check for all workspaces from current folder\n\
+               \n\
+               NOTE: for shallow or deep, rtool will enter each member\n\
+               folder to do the check.",
+    },
+    EnvVar {
+        name: "RTOOL_JOBS",
+        help: "number of members checked at the same time for shallow or\n\
+               deep RTOOL_RECURSIVE, the number of CPUs by default; their\n\
+               log lines start with [member name]",
+    },
+    EnvVar {
+        name: "RTOOL_ANALYZE_CRATE_TYPES",
+        help: "comma-separated crate kinds to analyze, e.g. lib,bin,proc-macro\n\
+               * kinds: lib, bin, proc-macro, custom-build (build scripts),\n  \
+               test, or any other --crate-type value\n\
+               * not set: analyze all local crates except proc-macro crates\n  \
+               and build scripts",
+    },
+    EnvVar {
+        name: "NO_COLOR",
+        help: "when set and not empty, -color auto doesn't color the mir",
+    },
+    EnvVar {
+        name: "RTOOL_WRAPPER_LOG",
+        help: "file to record whether each crate compiled by cargo check\n\
+               was handled by rtool or by plain rustc",
+    },
+];
+
+pub fn find_option(name: &str) -> Option<&'static RtoolOption> {
+    RTOOL_OPTIONS.iter().find(|option| option.name == name)
+}
+
+/// The closest option name to an unknown `-flag`, if any is close enough to be a typo.
+pub fn suggest_option(name: &str) -> Option<&'static str> {
+    if !name.starts_with('-') || name.starts_with("--") || name.len() < 4 {
+        return None;
+    }
+    RTOOL_OPTIONS
+        .iter()
+        .map(|option| (edit_distance(name, option.name), option.name))
+        .filter(|(distance, _)| *distance <= 2)
+        .min_by_key(|(distance, _)| *distance)
+        .map(|(_, name)| name)
+}
+
+fn edit_distance(a: &str, b: &str) -> usize {
+    let b: Vec<char> = b.chars().collect();
+    let mut prev: Vec<usize> = (0..=b.len()).collect();
+    for (i, ca) in a.chars().enumerate() {
+        let mut cur = vec![i + 1; b.len() + 1];
+        for (j, cb) in b.iter().enumerate() {
+            let substitution = prev[j] + usize::from(ca != *cb);
+            cur[j + 1] = substitution.min(prev[j + 1] + 1).min(cur[j] + 1);
+        }
+        prev = cur;
+    }
+    prev[b.len()]
+}
+
+fn usage(option: &RtoolOption) -> String {
+    match option.value {
+        Some(value) => format!("{} {}", option.name, value),
+        None => option.name.to_string(),
+    }
+}
+
+/// Append `text` starting at the current line, indenting its following lines by `indent`.
+fn push_indented(s: &mut String, text: &str, indent: usize) {
+    for (i, line) in text.lines().enumerate() {
+        if i > 0 {
+            s.push('\n');
+            if !line.is_empty() {
+                s.push_str(&" ".repeat(indent));
+            }
+        }
+        s.push_str(line);
+    }
+    s.push('\n');
+}
+
+const GROUPS: [OptionGroup; 4] = [
+    OptionGroup::Analysis,
+    OptionGroup::Output,
+    OptionGroup::Workspace,
+    OptionGroup::General,
+];
+
+/// The text printed by `cargo rtool -help`.
+pub fn help_text() -> String {
+    let mut s = String::from(
+        "\nUsage:\n    cargo rtool [rtool options] -- [cargo check options]\n\nrtool Options:\n",
+    );
+    for group in GROUPS {
+        s += &format!("\n{}:\n", group.title());
+        for option in RTOOL_OPTIONS.iter().filter(|option| option.group == group) {
+            let usage = usage(option);
+            if usage.len() < 20 {
+                s += &format!("    {:<20}", usage);
+            } else {
+                s += &format!("    {}\n{}", usage, " ".repeat(24));
+            }
+            push_indented(&mut s, option.help, 24);
+        }
+    }
+    s += "\nNOTE: multiple detections can be processed in single run by \n\
+          appending the options to the arguments.\n";
+    s += &format!(
+        "\nConfig File:\n    {}\n",
+        CONFIG_HELP.replace('\n', "\n    ")
+    );
+    s += "\nEnvironment Variables (Values are case insensitive):\n";
+    for var in RTOOL_ENV_VARS {
+        if var.name.len() < 17 {
+            s += &format!("    {:<19}", var.name);
+        } else {
+            s += &format!("    {}\n{}", var.name, " ".repeat(23));
+        }
+        push_indented(&mut s, var.help, 23);
+        s.push('\n');
+    }
+    s
+}
+
+/// Where cargo-rtool reads option defaults from, see `config.rs` of cargo-rtool.
+pub const CONFIG_HELP: &str = "An rtool.toml at the workspace root sets defaults for cargo rtool:\n\
+    allmir = true, mir = [\"fn\"], mirexact = [\"fn\"], outpath = \"file\",\n\
+    recursive = \"shallow\" and log = \"debug\". Options on the command line\n\
+    override it, targets replacing all of its targets, and so do the\n\
+    RTOOL_RECURSIVE and RTOOL_LOG environment variables.";
+
+/// The help as markdown, for the documentation.
+pub fn help_markdown() -> String {
+    let escape = |text: &str| text.replace('|', "\\|").replace('\n', "<br>");
+    let mut s =
+        String::from("# rtool\n\n```\ncargo rtool [rtool options] -- [cargo check options]\n```\n");
+    for group in GROUPS {
+        s += &format!(
+            "\n## {}\n\n| Option | Description |\n| --- | --- |\n",
+            group.title()
+        );
+        for option in RTOOL_OPTIONS.iter().filter(|option| option.group == group) {
+            s += &format!("| `{}` | {} |\n", usage(option), escape(option.help));
+        }
+    }
+    s += &format!("\n## Config File\n\n{}\n", CONFIG_HELP.replace('\n', " "));
+    s += "\n## Environment Variables\n\n| Variable | Description |\n| --- | --- |\n";
+    for var in RTOOL_ENV_VARS {
+        s += &format!("| `{}` | {} |\n", var.name, escape(var.help));
+    }
+    s
+}
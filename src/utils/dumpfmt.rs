@@ -0,0 +1,163 @@
+//! The plain text MIR dump written by `-mir`/`-mirexact`, for tools parsing it.
+//!
+//! A dump starts with a header line naming the format version, followed by one
+//! section per function. Each section starts with `fn <def path>`. When several crates
+//! share one output file, each crate's sections are preceded by `crate <name>`.
+//! The `fn` line of a generic function is followed by `generics <T, ...>`. With
+//! `-mirinstances`, its instances follow in their own sections, headed by their def
+//! path with generic args, e.g. `fn ident::<u32>`. The `fn` line of the initializer of
+//! a const or static is followed by its kind and declared type, e.g. `static
+//! SpinLock<u32>` or `const usize`.
+//! With `-mirpromoted`, the bodies promoted out of a function follow in its section, each
+//! starting with `promoted[N]`. Each basic block starts with `bb N {`, or
+//! `(cleanup) bb N {` for the blocks only run on unwind. With `-mirsource`, each basic
+//! block starts with the source lines it comes from, as `//` comments. Each terminator is
+//! followed by the blocks control flow goes to, e.g. `@ Goto -> bb3` or `@ Call: f ->
+//! [return: bb4, unwind: bb9]`. A `Drop` names the dropped place and its type, e.g. `@
+//! Drop(_4: SpinLockGuard<Foo>) -> [return: bb6, unwind: bb9]`. The values of a
+//! `SwitchInt` are decoded, e.g. `[None: bb2, Some: bb3, otherwise: bb1]` or `[false:
+//! bb7, otherwise: bb6]`. An assignment of a `Use`, `Repeat` or `BinaryOp` with a
+//! constant operand ends with its operands, the constants decoded, e.g. `@ Use: const
+//! "hello"` or `@ Use: const <unevaluated>`. With `-mircfg`, the basic blocks are
+//! followed by one `bbN idom bbM` line per block, ending with `loop-header` for loop
+//! headers, with `bb0 entry` and `bbN unreachable` for the entry and the unreachable
+//! blocks.
+//!
+//! A section ends with the source info of its basic blocks: one `bbN at <span>` line per
+//! block, or with `-mirspans` one `bbN[i] at <span>` line per statement and terminator.
+//!
+//! A function already dumped by another crate of the same run is recorded as a single
+//! `ref <crate> <def path>` line instead, pointing at the crate holding its section.
+//! The crates of a run agree on what was dumped through a manifest cargo-rtool keeps in
+//! the temp folder for the run, see `claim_section`.
+//!
+//! Bump `DUMP_FORMAT_VERSION` whenever the layout of a section changes.
+
+use std::fs::OpenOptions;
+use std::io::{self, Read, Write};
+use std::path::Path;
+
+pub const DUMP_FORMAT_VERSION: u32 = 12;
+
+const HEADER_PREFIX: &str = "# rtool-dump v";
+const CRATE_PREFIX: &str = "crate ";
+const FN_PREFIX: &str = "fn ";
+const REF_PREFIX: &str = "ref ";
+
+/// The header line, without the trailing newline.
+pub fn header() -> String {
+    format!("{}{}", HEADER_PREFIX, DUMP_FORMAT_VERSION)
+}
+
+pub fn write_header(writer: &mut dyn Write) -> Result<(), io::Error> {
+    writeln!(writer, "{}", header())
+}
+
+pub fn write_crate_line(writer: &mut dyn Write, crate_name: &str) -> Result<(), io::Error> {
+    writeln!(writer, "{}{}", CRATE_PREFIX, crate_name)
+}
+
+pub fn write_cross_reference(
+    writer: &mut dyn Write,
+    dumped_in: &str,
+    fn_name: &str,
+) -> Result<(), io::Error> {
+    writeln!(writer, "{}{} {}", REF_PREFIX, dumped_in, fn_name)
+}
+
+/// Parse the version from a header line.
+pub fn parse_header(line: &str) -> Option<u32> {
+    line.trim_end().strip_prefix(HEADER_PREFIX)?.parse().ok()
+}
+
+/// The dump of one function.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct DumpSection<'a> {
+    /// The crate line in effect, if the dump has one.
+    pub crate_name: Option<&'a str>,
+    pub fn_name: &'a str,
+    /// Lines following the `fn` line, up to the next section.
+    pub lines: Vec<&'a str>,
+    /// For a `ref` line, the crate whose section holds the dump; `lines` is empty then.
+    pub dumped_in: Option<&'a str>,
+}
+
+/// Split a dump into its function sections.
+///
+/// Fails if the dump doesn't start with a header of the current version.
+pub fn split_sections(dump: &str) -> Result<Vec<DumpSection<'_>>, String> {
+    let mut lines = dump.lines();
+    match lines.next().map(parse_header) {
+        Some(Some(DUMP_FORMAT_VERSION)) => {}
+        Some(Some(version)) => {
+            return Err(format!(
+                "unsupported dump format v{}, expected v{}",
+                version, DUMP_FORMAT_VERSION
+            ));
+        }
+        _ => return Err("missing rtool-dump header".to_string()),
+    }
+
+    let mut sections: Vec<DumpSection> = vec![];
+    let mut crate_name = None;
+    for line in lines {
+        if let Some(name) = line.strip_prefix(CRATE_PREFIX) {
+            crate_name = Some(name);
+        } else if let Some(name) = line.strip_prefix(FN_PREFIX) {
+            sections.push(DumpSection {
+                crate_name,
+                fn_name: name,
+                lines: vec![],
+                dumped_in: None,
+            });
+        } else if let Some(reference) = line.strip_prefix(REF_PREFIX) {
+            let Some((dumped_in, name)) = reference.split_once(' ') else {
+                return Err(format!("malformed ref line: {}", line));
+            };
+            sections.push(DumpSection {
+                crate_name,
+                fn_name: name,
+                lines: vec![],
+                dumped_in: Some(dumped_in),
+            });
+        } else if let Some(section) = sections.last_mut() {
+            section.lines.push(line);
+        } else if !line.trim().is_empty() {
+            return Err(format!(
+                "unexpected line before the first function: {}",
+                line
+            ));
+        }
+    }
+    Ok(sections)
+}
+
+/// Claim the dump of a function for `crate_name` in the manifest.
+///
+/// Each manifest line is `<key> <body hash> <crate>`. Returns the crate that already
+/// dumped the same body under `key`, or None if the caller should dump it. The manifest
+/// is locked meanwhile, since the crates of a run are compiled concurrently.
+pub fn claim_section(
+    manifest: &Path,
+    key: &str,
+    body_hash: u64,
+    crate_name: &str,
+) -> Result<Option<String>, io::Error> {
+    let mut file = OpenOptions::new()
+        .read(true)
+        .append(true)
+        .create(true)
+        .open(manifest)?;
+    file.lock()?;
+    let mut entries = String::new();
+    file.read_to_string(&mut entries)?;
+    let body_hash = format!("{:016x}", body_hash);
+    for entry in entries.lines() {
+        let mut fields = entry.splitn(3, ' ');
+        if fields.next() == Some(key) && fields.next() == Some(body_hash.as_str()) {
+            return Ok(fields.next().map(str::to_string));
+        }
+    }
+    writeln!(file, "{} {} {}", key, body_hash, crate_name)?;
+    Ok(None)
+}
@@ -1,7 +1,7 @@
 use crate::utils::log::rtool_error_and_exit;
 
 use std::fs::{self, File};
-use std::io::Write;
+use std::io::{self, Write};
 use std::path::Path;
 
 use rustc_demangle::try_demangle;
@@ -56,3 +56,54 @@ pub fn rtool_demangle(name: &str) -> String {
         Err(_) => name.to_string(),
     }
 }
+
+/// Check that `path` can be used as an output file before any analysis starts:
+/// it must not be a directory, and it must be writable. A missing parent folder is
+/// created.
+pub fn rtool_check_output_file<P: AsRef<Path>>(path: P) -> Result<(), String> {
+    let path = path.as_ref();
+    if path.is_dir() {
+        return Err(format!("{} is a directory", path.display()));
+    }
+    if let Some(parent) = path.parent()
+        && !parent.as_os_str().is_empty()
+        && !parent.is_dir()
+    {
+        fs::create_dir_all(parent).map_err(|e| {
+            format!(
+                "the parent folder of {} can't be created: {}",
+                path.display(),
+                e
+            )
+        })?;
+    }
+    fs::OpenOptions::new()
+        .append(true)
+        .create(true)
+        .open(path)
+        .map(|_| ())
+        .map_err(|e| format!("{} is not writable: {}", path.display(), e))
+}
+
+/// Check that `path` can be used as an output folder: it must be a folder if it exists.
+/// A missing folder is created when the output is written.
+pub fn rtool_check_output_dir<P: AsRef<Path>>(path: P) -> Result<(), String> {
+    let path = path.as_ref();
+    if path.exists() && !path.is_dir() {
+        return Err(format!("{} is not a directory", path.display()));
+    }
+    Ok(())
+}
+
+/// Append `lines` to `path` at once, under a lock, as the crates of a cargo-rtool run
+/// are compiled concurrently.
+pub fn rtool_append_lines<P: AsRef<Path>>(path: P, lines: &[String]) -> io::Result<()> {
+    let mut file = fs::OpenOptions::new()
+        .append(true)
+        .create(true)
+        .open(path)?;
+    file.lock()?;
+    let text: String = lines.iter().map(|line| format!("{}\n", line)).collect();
+    file.write_all(text.as_bytes())?;
+    file.flush()
+}
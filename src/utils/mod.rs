@@ -1,3 +1,4 @@
+pub mod dumpfmt;
 pub mod fs;
 pub mod log;
 pub mod source;
@@ -25,41 +25,41 @@ pub fn get_fn_name(tcx: TyCtxt<'_>, def_id: DefId) -> Option<String> {
 
 pub fn get_fn_name_byid(def_id: &DefId) -> String {
     let s = format!("{:?}", *def_id);
-    if let Some(start) = s.find("DefId") {
-        if let Some(end) = s.find("]::") {
-            let s1 = s.replace(&s[start..end + 3], "").to_string();
-            if let Some(start) = s1.find(")") {
-                let result = s1.replace(&s1[start..start + 1], "").to_string();
-                return result;
-            }
-            return s1;
+    if let Some(start) = s.find("DefId")
+        && let Some(end) = s.find("]::")
+    {
+        let s1 = s.replace(&s[start..end + 3], "").to_string();
+        if let Some(start) = s1.find(")") {
+            let result = s1.replace(&s1[start..start + 1], "").to_string();
+            return result;
         }
+        return s1;
     }
     s.clone()
 }
 pub fn get_name(tcx: TyCtxt<'_>, def_id: DefId) -> Option<Symbol> {
-    if def_id.is_local() {
-        if let Some(node) = tcx.hir_get_if_local(def_id) {
-            match node {
-                Item(item) => {
-                    let ident = tcx.hir_ident(item.hir_id());
-                    return Some(ident.name);
-                }
-                ImplItem(item) => {
-                    let ident = tcx.hir_ident(item.hir_id());
-                    return Some(ident.name);
-                }
-                ForeignItem(item) => {
-                    let ident = tcx.hir_ident(item.hir_id());
-                    return Some(ident.name);
-                }
-                TraitItem(item) => {
-                    let ident = tcx.hir_ident(item.hir_id());
-                    return Some(ident.name);
-                }
-                _ => {
-                    return None;
-                }
+    if def_id.is_local()
+        && let Some(node) = tcx.hir_get_if_local(def_id)
+    {
+        match node {
+            Item(item) => {
+                let ident = tcx.hir_ident(item.hir_id());
+                return Some(ident.name);
+            }
+            ImplItem(item) => {
+                let ident = tcx.hir_ident(item.hir_id());
+                return Some(ident.name);
+            }
+            ForeignItem(item) => {
+                let ident = tcx.hir_ident(item.hir_id());
+                return Some(ident.name);
+            }
+            TraitItem(item) => {
+                let ident = tcx.hir_ident(item.hir_id());
+                return Some(ident.name);
+            }
+            _ => {
+                return None;
             }
         }
     }
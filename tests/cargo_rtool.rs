@@ -0,0 +1,642 @@
+mod common;
+
+use common::{Fixture, package_manifest, run};
+use std::process::Command;
+
+#[test]
+fn single_crate_dumps_mir_to_outpath() {
+    let fixture = Fixture::new("single_crate")
+        .file("Cargo.toml", &package_manifest("single"))
+        .file("src/main.rs", "fn main() {\n    let _x = 1 + 2;\n}\n");
+
+    run(fixture.cargo_rtool(&["-mir", "main", "-outpath", "mir.txt"], &["--offline"]));
+
+    let dump = fixture.read("mir.txt");
+    assert!(dump.contains("crate single"), "{dump}");
+    assert!(dump.contains("fn main"), "{dump}");
+}
+
+#[test]
+fn config_file_sets_defaults_the_command_line_overrides() {
+    let fixture = Fixture::new("config_file")
+        .file("Cargo.toml", &package_manifest("config_file"))
+        .file(
+            "src/main.rs",
+            "fn main() {\n    other();\n}\n\nfn other() {}\n",
+        )
+        .file(
+            "rtool.toml",
+            "mirexact = [\"main\"]\noutpath = \"mir.txt\"\n",
+        );
+
+    run(fixture.cargo_rtool(&[], &["--offline"]));
+    let dump = fixture.read("mir.txt");
+    assert!(dump.contains("fn main\n"), "{dump}");
+    assert!(!dump.contains("fn other\n"), "{dump}");
+
+    run(fixture.cargo_rtool(&["-mirexact", "other"], &["--offline"]));
+    let dump = fixture.read("mir.txt");
+    assert!(dump.contains("fn other\n"), "{dump}");
+    assert!(!dump.contains("fn main\n"), "{dump}");
+}
+
+#[test]
+fn invalid_config_file_fails_before_checking() {
+    let fixture = Fixture::new("invalid_config_file")
+        .file("Cargo.toml", &package_manifest("invalid_config_file"))
+        .file("src/main.rs", "fn main() {}\n")
+        .file("rtool.toml", "mri = [\"main\"]\n");
+
+    let output = fixture.cargo_rtool(&[], &["--offline"]).output().unwrap();
+    assert!(!output.status.success());
+    let stderr = String::from_utf8_lossy(&output.stderr);
+    assert!(
+        stderr.contains("rtool.toml: unknown field `mri`"),
+        "{stderr}"
+    );
+    assert!(!fixture.path("target").exists());
+}
+
+#[test]
+fn shallow_run_appends_every_member() {
+    let fixture = Fixture::new("two_members")
+        .file(
+            "Cargo.toml",
+            "[workspace]\nmembers = [\"first\", \"second\"]\nresolver = \"2\"\n",
+        )
+        .file(
+            "first/Cargo.toml",
+            "[package]\nname = \"first\"\nversion = \"0.1.0\"\nedition = \"2021\"\n",
+        )
+        .file("first/src/lib.rs", "pub fn in_first() -> u32 {\n    1\n}\n")
+        .file(
+            "second/Cargo.toml",
+            "[package]\nname = \"second\"\nversion = \"0.1.0\"\nedition = \"2021\"\n",
+        )
+        .file(
+            "second/src/lib.rs",
+            "pub fn in_second() -> u32 {\n    2\n}\n",
+        );
+
+    let mut cmd = fixture.cargo_rtool(&["-mir", "in_", "-outpath", "mir.txt"], &["--offline"]);
+    cmd.env("RTOOL_RECURSIVE", "shallow");
+    run(cmd);
+
+    let dump = fixture.read("mir.txt");
+    assert!(dump.contains("fn in_first"), "{dump}");
+    assert!(dump.contains("fn in_second"), "{dump}");
+}
+
+#[test]
+fn build_scripts_and_proc_macros_use_plain_rustc() {
+    let fixture = Fixture::new("special_crate_types")
+        .file(
+            "Cargo.toml",
+            r#"[package]
+name = "user"
+version = "0.1.0"
+edition = "2021"
+
+[dependencies]
+helper_macro = { path = "helper_macro" }
+
+[workspace]
+members = ["helper_macro"]
+"#,
+        )
+        .file("build.rs", "fn main() {}\n")
+        .file(
+            "src/main.rs",
+            "helper_macro::nothing!();\n\nfn main() {\n    let _x = 1;\n}\n",
+        )
+        .file(
+            "helper_macro/Cargo.toml",
+            r#"[package]
+name = "helper_macro"
+version = "0.1.0"
+edition = "2021"
+
+[lib]
+proc-macro = true
+"#,
+        )
+        .file(
+            "helper_macro/src/lib.rs",
+            r#"extern crate proc_macro;
+use proc_macro::TokenStream;
+
+#[proc_macro]
+pub fn nothing(_input: TokenStream) -> TokenStream {
+    TokenStream::new()
+}
+"#,
+        );
+
+    let log = fixture.path("wrapper.log");
+    let mut cmd = fixture.cargo_rtool(&["-mir", "main", "-outpath", "mir.txt"], &["--offline"]);
+    cmd.env("RTOOL_WRAPPER_LOG", &log);
+    run(cmd);
+
+    let log = fixture.read("wrapper.log");
+    assert!(log.lines().any(|l| l == "rtool user"), "{log}");
+    assert!(
+        log.lines().any(|l| l == "rustc build_script_build"),
+        "{log}"
+    );
+    assert!(log.lines().any(|l| l == "rustc helper_macro"), "{log}");
+    assert!(!log.lines().any(|l| l == "rtool helper_macro"), "{log}");
+
+    let dump = fixture.read("mir.txt");
+    assert!(dump.contains("fn main"), "{dump}");
+}
+
+#[test]
+fn proc_macros_and_build_scripts_are_analyzed_on_request() {
+    let fixture = Fixture::new("analyze_proc_macro")
+        .file(
+            "Cargo.toml",
+            r#"[package]
+name = "my_macro"
+version = "0.1.0"
+edition = "2021"
+
+[lib]
+proc-macro = true
+
+[workspace]
+"#,
+        )
+        .file(
+            "build.rs",
+            "fn main() {\n    build_helper();\n}\n\nfn build_helper() {}\n",
+        )
+        .file(
+            "src/lib.rs",
+            r#"extern crate proc_macro;
+use proc_macro::TokenStream;
+
+#[proc_macro]
+pub fn macro_entry(input: TokenStream) -> TokenStream {
+    input
+}
+"#,
+        );
+
+    let log = fixture.path("wrapper.log");
+    let mut cmd = fixture.cargo_rtool(
+        &[
+            "-mir",
+            "macro_entry",
+            "-mir",
+            "build_helper",
+            "-outpath",
+            "mir.txt",
+        ],
+        &["--offline"],
+    );
+    cmd.env("RTOOL_WRAPPER_LOG", &log)
+        .env("RTOOL_ANALYZE_CRATE_TYPES", "proc-macro,custom-build");
+    run(cmd);
+
+    let log = fixture.read("wrapper.log");
+    assert!(log.lines().any(|l| l == "rtool my_macro"), "{log}");
+    assert!(
+        log.lines().any(|l| l == "rtool build_script_build"),
+        "{log}"
+    );
+
+    let dump = fixture.read("mir.txt");
+    assert!(dump.contains("fn macro_entry"), "{dump}");
+    assert!(dump.contains("fn build_helper"), "{dump}");
+}
+
+#[test]
+fn version_names_the_toolchain() {
+    let fixture = Fixture::new("version");
+
+    let output = run(fixture.cargo_rtool(&["-version"], &[]));
+    let stderr = String::from_utf8_lossy(&output.stderr);
+    assert!(
+        stderr.contains(&format!("rtool version {} (", env!("CARGO_PKG_VERSION"))),
+        "{stderr}"
+    );
+    assert!(stderr.contains("built with rustc 1."), "{stderr}");
+}
+
+#[test]
+fn invalid_outpath_fails_before_checking() {
+    let fixture = Fixture::new("invalid_outpath")
+        .file("Cargo.toml", &package_manifest("invalid_outpath"))
+        .file("src/main.rs", "fn main() {}\n");
+
+    let output = fixture
+        .cargo_rtool(
+            &["-mir", "main", "-outpath", "src/main.rs/mir.txt"],
+            &["--offline"],
+        )
+        .output()
+        .unwrap();
+    assert!(!output.status.success());
+    let stderr = String::from_utf8_lossy(&output.stderr);
+    assert!(stderr.contains("can't be created"), "{stderr}");
+    assert!(!fixture.path("target").exists());
+}
+
+#[test]
+fn missing_outpath_folders_are_created() {
+    let fixture = Fixture::new("missing_outpath_folders")
+        .file("Cargo.toml", &package_manifest("missing_outpath_folders"))
+        .file("src/main.rs", "fn main() {}\n");
+
+    run(fixture.cargo_rtool(
+        &["-mir", "main", "-outpath", "missing/folders/mir.txt"],
+        &["--offline"],
+    ));
+    let dump = fixture.read("missing/folders/mir.txt");
+    assert!(dump.contains("fn main"), "{dump}");
+}
+
+#[test]
+fn invalid_regex_fails_before_checking() {
+    let fixture = Fixture::new("invalid_regex")
+        .file("Cargo.toml", &package_manifest("invalid_regex"))
+        .file("src/main.rs", "fn main() {}\n");
+
+    let output = fixture
+        .cargo_rtool(&["-mirregex", "main("], &["--offline"])
+        .output()
+        .unwrap();
+    assert!(!output.status.success());
+    let stderr = String::from_utf8_lossy(&output.stderr);
+    assert!(
+        stderr.contains("Invalid -mirregex: invalid regex main("),
+        "{stderr}"
+    );
+    assert!(!fixture.path("target").exists());
+}
+
+#[test]
+fn serve_is_rejected_before_checking() {
+    let fixture = Fixture::new("serve_rejected")
+        .file("Cargo.toml", &package_manifest("serve_rejected"))
+        .file("src/main.rs", "fn main() {}\n");
+
+    let output = fixture
+        .cargo_rtool(&["-serve"], &["--offline"])
+        .output()
+        .unwrap();
+    assert!(!output.status.success());
+    let stderr = String::from_utf8_lossy(&output.stderr);
+    assert!(
+        stderr.contains("-serve is only supported by the rtool binary"),
+        "{stderr}"
+    );
+    assert!(!fixture.path("target").exists());
+}
+
+#[test]
+fn targets_are_reported_once_for_every_crate() {
+    let fixture = Fixture::new("unmatched_across_crates")
+        .file(
+            "Cargo.toml",
+            "[workspace]\nmembers = [\"a\", \"b\"]\nresolver = \"2\"\n",
+        )
+        .file(
+            "a/Cargo.toml",
+            "[package]\nname = \"a\"\nversion = \"0.1.0\"\nedition = \"2021\"\n",
+        )
+        .file("a/src/lib.rs", "pub fn only_in_a() {}\n")
+        .file(
+            "b/Cargo.toml",
+            "[package]\nname = \"b\"\nversion = \"0.1.0\"\nedition = \"2021\"\n",
+        )
+        .file("b/src/lib.rs", "pub fn only_in_b() {}\n");
+    let rtool_args = [
+        "-mirexact",
+        "only_in_a",
+        "-mirexact",
+        "only_in_b",
+        "-mirexact",
+        "nowhere",
+        "-strict-match",
+        "-outpath",
+        "mir.txt",
+    ];
+
+    let mut cmd = fixture.cargo_rtool(&rtool_args, &["--offline"]);
+    cmd.env("RTOOL_RECURSIVE", "shallow");
+    let output = cmd.output().unwrap();
+    assert!(!output.status.success());
+    let stderr = String::from_utf8_lossy(&output.stderr);
+    assert_eq!(stderr.matches("No fn matched").count(), 1, "{stderr}");
+    assert!(
+        stderr.contains("No fn matched -mirexact nowhere"),
+        "{stderr}"
+    );
+    let dump = fixture.read("mir.txt");
+    assert!(dump.contains("fn only_in_a"), "{dump}");
+    assert!(dump.contains("fn only_in_b"), "{dump}");
+}
+
+#[test]
+fn members_are_checked_in_parallel() {
+    // Each build script waits for the one of the other member, which only finishes
+    // when both members are checked at the same time.
+    let build = r#"use std::{env, fs, path::Path, thread, time::Duration};
+
+fn main() {
+    let markers = env::var("MARKERS").unwrap();
+    let name = env::var("CARGO_PKG_NAME").unwrap();
+    let other = if name == "one" { "two" } else { "one" };
+    fs::write(Path::new(&markers).join(&name), "").unwrap();
+    for _ in 0..300 {
+        if Path::new(&markers).join(other).exists() {
+            return;
+        }
+        thread::sleep(Duration::from_millis(100));
+    }
+    panic!("{other} was not checked alongside {name}");
+}
+"#;
+    let mut fixture = Fixture::new("overlapping_members")
+        .file(
+            "Cargo.toml",
+            "[workspace]\nmembers = [\"one\", \"two\"]\nresolver = \"2\"\n",
+        )
+        .file("markers/.keep", "");
+    for name in ["one", "two"] {
+        fixture = fixture
+            .file(
+                &format!("{name}/Cargo.toml"),
+                &format!("[package]\nname = \"{name}\"\nversion = \"0.1.0\"\nedition = \"2021\"\n"),
+            )
+            .file(&format!("{name}/build.rs"), build)
+            .file(
+                &format!("{name}/src/lib.rs"),
+                &format!("pub fn in_{name}() {{}}\n"),
+            );
+    }
+
+    let mut cmd = fixture.cargo_rtool(&["-mir", "in_", "-outpath", "mir.txt"], &["--offline"]);
+    cmd.env("RTOOL_RECURSIVE", "shallow")
+        .env("RTOOL_JOBS", "2")
+        .env("MARKERS", fixture.path("markers"));
+    run(cmd);
+    let dump = fixture.read("mir.txt");
+    assert!(dump.contains("fn in_one"), "{dump}");
+    assert!(dump.contains("fn in_two"), "{dump}");
+}
+
+#[test]
+fn failing_member_does_not_stop_the_others() {
+    let fixture = Fixture::new("parallel_members")
+        .file(
+            "Cargo.toml",
+            "[workspace]\nmembers = [\"good\", \"bad\"]\nresolver = \"2\"\n",
+        )
+        .file(
+            "good/Cargo.toml",
+            "[package]\nname = \"good\"\nversion = \"0.1.0\"\nedition = \"2021\"\n",
+        )
+        .file("good/src/lib.rs", "pub fn in_good() {}\n")
+        .file(
+            "bad/Cargo.toml",
+            "[package]\nname = \"bad\"\nversion = \"0.1.0\"\nedition = \"2021\"\n",
+        )
+        .file(
+            "bad/src/lib.rs",
+            "pub fn in_bad() -> u32 {\n    \"no\"\n}\n",
+        );
+
+    let mut cmd = fixture.cargo_rtool(&["-mir", "in_", "-outpath", "mir.txt"], &["--offline"]);
+    cmd.env("RTOOL_RECURSIVE", "shallow").env("RTOOL_JOBS", "2");
+    let output = cmd.output().unwrap();
+    assert!(!output.status.success());
+    let stderr = String::from_utf8_lossy(&output.stderr);
+    assert!(stderr.contains("[bad] error[E0308]"), "{stderr}");
+    assert!(stderr.contains("cargo check failed in bad"), "{stderr}");
+    assert!(!stderr.contains("cargo check failed in good"), "{stderr}");
+    let dump = fixture.read("mir.txt");
+    assert!(dump.contains("fn in_good"), "{dump}");
+
+    let mut cmd = fixture.cargo_rtool(&[], &["--offline"]);
+    cmd.env("RTOOL_RECURSIVE", "shallow").env("RTOOL_JOBS", "0");
+    let output = cmd.output().unwrap();
+    assert!(!output.status.success());
+    let stderr = String::from_utf8_lossy(&output.stderr);
+    assert!(stderr.contains("`RTOOL_JOBS` should be"), "{stderr}");
+}
+
+#[test]
+fn parallel_members_prefix_their_stdout() {
+    let mut fixture = Fixture::new("parallel_stdout").file(
+        "Cargo.toml",
+        "[workspace]\nmembers = [\"one\", \"two\"]\nresolver = \"2\"\n",
+    );
+    for name in ["one", "two"] {
+        fixture = fixture
+            .file(
+                &format!("{name}/Cargo.toml"),
+                &format!("[package]\nname = \"{name}\"\nversion = \"0.1.0\"\nedition = \"2021\"\n"),
+            )
+            .file(
+                &format!("{name}/src/lib.rs"),
+                &format!("pub fn in_{name}() {{}}\n"),
+            );
+    }
+
+    let mut cmd = fixture.cargo_rtool(&["-mir", "in_", "-nocolor"], &["--offline"]);
+    cmd.env("RTOOL_RECURSIVE", "shallow").env("RTOOL_JOBS", "2");
+    let output = run(cmd);
+
+    let stdout = String::from_utf8_lossy(&output.stdout);
+    assert!(stdout.contains("[one] fn in_one\n"), "{stdout}");
+    assert!(stdout.contains("[two] fn in_two\n"), "{stdout}");
+    for line in stdout.lines() {
+        assert!(
+            line.starts_with("[one] ") || line.starts_with("[two] "),
+            "{line} in {stdout}"
+        );
+    }
+}
+
+#[test]
+fn only_and_exclude_pick_members() {
+    let mut fixture = Fixture::new("picked_members").file(
+        "Cargo.toml",
+        "[workspace]\nmembers = [\"one\", \"two\", \"three\"]\nresolver = \"2\"\n",
+    );
+    for name in ["one", "two", "three"] {
+        fixture = fixture
+            .file(
+                &format!("{name}/Cargo.toml"),
+                &format!("[package]\nname = \"{name}\"\nversion = \"0.1.0\"\nedition = \"2021\"\n"),
+            )
+            .file(
+                &format!("{name}/src/lib.rs"),
+                &format!("pub fn in_{name}() {{}}\n"),
+            );
+    }
+
+    let rtool_args = [
+        "-mir", "in_", "-only", "one", "-only", "two", "-only", "four", "-exclude", "two",
+        "-outpath", "mir.txt",
+    ];
+    let mut cmd = fixture.cargo_rtool(&rtool_args, &["--offline"]);
+    cmd.env("RTOOL_RECURSIVE", "shallow");
+    let output = cmd.output().unwrap();
+    assert!(output.status.success());
+    let stderr = String::from_utf8_lossy(&output.stderr);
+    assert!(
+        stderr.contains("-only four matched no workspace member"),
+        "{stderr}"
+    );
+    assert!(!stderr.contains("-only two matched"), "{stderr}");
+    assert!(!stderr.contains("-exclude two matched"), "{stderr}");
+    let dump = fixture.read("mir.txt");
+    assert!(dump.contains("fn in_one"), "{dump}");
+    assert!(!dump.contains("fn in_two"), "{dump}");
+    assert!(!dump.contains("fn in_three"), "{dump}");
+}
+
+/// The target the tests are compiled for, as rustc names it.
+fn host_target() -> String {
+    let mut cmd = Command::new("rustc");
+    cmd.arg("-vV");
+    let output = run(cmd);
+    String::from_utf8_lossy(&output.stdout)
+        .lines()
+        .find_map(|line| line.strip_prefix("host: "))
+        .expect("rustc -vV names the host")
+        .to_string()
+}
+
+#[test]
+fn cargo_args_reach_cargo_check() {
+    let fixture = Fixture::new("cargo_args")
+        .file(
+            "Cargo.toml",
+            &format!(
+                "{}\n[features]\ndefault = [\"dflt\"]\ndflt = []\nextra = []\n",
+                package_manifest("cargo_args")
+            ),
+        )
+        .file(
+            "src/lib.rs",
+            "#[cfg(feature = \"extra\")]\npub fn with_extra() {}\n\n\
+             #[cfg(feature = \"dflt\")]\npub fn with_default() {}\n",
+        );
+    let target = host_target();
+
+    run(fixture.cargo_rtool(
+        &["-mir", "with_", "-outpath", "mir.txt"],
+        &[
+            "--offline",
+            "--features",
+            "extra",
+            "--no-default-features",
+            "--target",
+            &target,
+        ],
+    ));
+
+    let dump = fixture.read("mir.txt");
+    assert!(dump.contains("fn with_extra\n"), "{dump}");
+    assert!(!dump.contains("fn with_default\n"), "{dump}");
+    assert!(fixture.path("target").join(&target).exists());
+
+    let output = fixture
+        .cargo_rtool(
+            &["-mir", "with_"],
+            &["--offline", "--target", "no-such-target"],
+        )
+        .output()
+        .unwrap();
+    assert!(!output.status.success());
+    let stderr = String::from_utf8_lossy(&output.stderr);
+    assert!(
+        stderr.contains("could not find specification for target \"no-such-target\""),
+        "{stderr}"
+    );
+}
+
+#[test]
+fn crate_with_errors_still_dumps_healthy_bodies() {
+    // Type errors stop rustc before the analysis, lint errors don't.
+    let fixture = Fixture::new("crate_with_errors").file(
+        "lib.rs",
+        "#![deny(unused_variables)]\n\npub fn broken() {\n    let x = 1;\n}\n\n\
+         pub fn healthy() -> u32 {\n    2\n}\n",
+    );
+
+    let output = fixture
+        .rtool(&[
+            "lib.rs",
+            "--crate-type",
+            "lib",
+            "-allmir",
+            "-mir",
+            "healthy",
+            "-outpath",
+            "mir.txt",
+        ])
+        .env("RTOOL_LOG", "info")
+        .output()
+        .expect("Failed to spawn rtool.");
+    assert!(!output.status.success());
+
+    let stderr = String::from_utf8_lossy(&output.stderr);
+    assert!(!stderr.contains("panicked"), "{stderr}");
+    assert!(stderr.contains("The crate has errors"), "{stderr}");
+    let dump = fixture.read("mir.txt");
+    assert!(dump.contains("fn healthy"), "{dump}");
+}
+
+#[test]
+fn outdir_writes_one_file_per_function() {
+    let fixture = Fixture::new("outdir")
+        .file("Cargo.toml", &package_manifest("outdir"))
+        .file(
+            "src/lib.rs",
+            "pub struct Wrapper<T>(T);\n\nimpl<T: Clone> Wrapper<T> {\n    \
+             pub fn get(&self) -> T {\n        self.0.clone()\n    }\n}\n\n\
+             pub fn get_answer() -> u32 {\n    Wrapper(42).get()\n}\n",
+        );
+
+    run(fixture.cargo_rtool(&["-mir", "get", "-outdir", "mir"], &["--offline"]));
+
+    let mut files: Vec<String> = std::fs::read_dir(fixture.path("mir"))
+        .unwrap()
+        .map(|entry| entry.unwrap().file_name().into_string().unwrap())
+        .collect();
+    files.sort();
+    assert_eq!(
+        files,
+        ["outdir.Wrapper._T_.get.txt", "outdir.get_answer.txt"]
+    );
+    let dump = fixture.read("mir/outdir.get_answer.txt");
+    assert!(dump.starts_with("# rtool-dump v"), "{dump}");
+    assert!(dump.contains("fn get_answer\n"), "{dump}");
+}
+
+#[test]
+fn outdir_and_outpath_are_exclusive() {
+    let fixture = Fixture::new("outdir_and_outpath")
+        .file("Cargo.toml", &package_manifest("outdir_and_outpath"))
+        .file("src/main.rs", "fn main() {}\n");
+
+    let output = fixture
+        .cargo_rtool(
+            &["-mir", "main", "-outpath", "mir.txt", "-outdir", "mir"],
+            &["--offline"],
+        )
+        .output()
+        .unwrap();
+    assert!(!output.status.success());
+    let stderr = String::from_utf8_lossy(&output.stderr);
+    assert!(
+        stderr.contains("-outdir can't be combined with -outpath"),
+        "{stderr}"
+    );
+    assert!(!fixture.path("target").exists());
+}
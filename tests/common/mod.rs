@@ -0,0 +1,91 @@
+#![allow(dead_code)]
+
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::process::{Command, Output};
+
+/// A throwaway cargo project under the integration test temp folder.
+///
+/// Fixtures live inside this repository's target folder, so cargo picks up the
+/// same `rust-toolchain.toml` that rtool was built with.
+pub struct Fixture {
+    root: PathBuf,
+}
+
+impl Fixture {
+    pub fn new(name: &str) -> Self {
+        let root = Path::new(env!("CARGO_TARGET_TMPDIR")).join(name);
+        if root.exists() {
+            fs::remove_dir_all(&root).expect("Failed to remove an old fixture.");
+        }
+        fs::create_dir_all(&root).expect("Failed to create the fixture folder.");
+        Fixture { root }
+    }
+
+    pub fn file(self, path: &str, contents: &str) -> Self {
+        let path = self.root.join(path);
+        fs::create_dir_all(path.parent().unwrap()).expect("Failed to create a fixture folder.");
+        fs::write(path, contents).expect("Failed to write a fixture file.");
+        self
+    }
+
+    pub fn path(&self, path: &str) -> PathBuf {
+        self.root.join(path)
+    }
+
+    pub fn read(&self, path: &str) -> String {
+        fs::read_to_string(self.path(path))
+            .unwrap_or_else(|e| panic!("Failed to read {}: {}", path, e))
+    }
+
+    /// `cargo rtool <rtool_args> -- <cargo_args>` run in the fixture root.
+    pub fn cargo_rtool(&self, rtool_args: &[&str], cargo_args: &[&str]) -> Command {
+        let mut cmd = Command::new(env!("CARGO_BIN_EXE_cargo-rtool"));
+        cmd.current_dir(&self.root)
+            .arg("rtool")
+            .args(rtool_args)
+            .arg("--")
+            .args(cargo_args)
+            .env_remove("RTOOL_RECURSIVE")
+            .env_remove("RTOOL_ANALYZE_CRATE_TYPES")
+            .env_remove("RTOOL_LOG");
+        cmd
+    }
+
+    /// `rtool <args>` run directly in the fixture root, without cargo.
+    pub fn rtool(&self, args: &[&str]) -> Command {
+        let mut cmd = Command::new(env!("CARGO_BIN_EXE_rtool"));
+        cmd.current_dir(&self.root)
+            .args(args)
+            .args(["--out-dir", "target"])
+            .env_remove("RTOOL_OUTPATH_APPEND")
+            .env_remove("RTOOL_LOG");
+        cmd
+    }
+}
+
+pub fn run(mut cmd: Command) -> Output {
+    let output = cmd.output().expect("Failed to spawn the command.");
+    if !output.status.success() {
+        panic!(
+            "{:?} failed with {}\nstdout:\n{}\nstderr:\n{}",
+            cmd,
+            output.status,
+            String::from_utf8_lossy(&output.stdout),
+            String::from_utf8_lossy(&output.stderr)
+        );
+    }
+    output
+}
+
+pub fn package_manifest(name: &str) -> String {
+    format!(
+        r#"[package]
+name = "{name}"
+version = "0.1.0"
+edition = "2021"
+
+[workspace]
+"#
+    )
+}
@@ -0,0 +1,105 @@
+#![feature(rustc_private)]
+
+extern crate rustc_driver;
+
+mod common;
+
+use common::{Fixture, package_manifest, run};
+use rtool::utils::dumpfmt::{self, DUMP_FORMAT_VERSION};
+
+#[test]
+fn header_round_trips() {
+    assert_eq!(
+        dumpfmt::parse_header(&dumpfmt::header()),
+        Some(DUMP_FORMAT_VERSION)
+    );
+    assert_eq!(dumpfmt::parse_header("fn main"), None);
+    assert!(dumpfmt::split_sections("# rtool-dump v1\nfn main\n").is_err());
+    assert!(dumpfmt::split_sections("fn main\n").is_err());
+}
+
+#[test]
+fn dump_splits_into_function_sections() {
+    let fixture = Fixture::new("dumpfmt_sections")
+        .file("Cargo.toml", &package_manifest("sections"))
+        .file(
+            "src/lib.rs",
+            "pub fn first() -> u32 {\n    1\n}\n\npub fn second() -> u32 {\n    first() + 1\n}\n",
+        );
+
+    run(fixture.cargo_rtool(
+        &[
+            "-mirexact",
+            "first",
+            "-mirexact",
+            "second",
+            "-outpath",
+            "mir.txt",
+        ],
+        &["--offline"],
+    ));
+
+    let dump = fixture.read("mir.txt");
+    let mut sections = dumpfmt::split_sections(&dump).unwrap();
+    sections.sort_by_key(|section| section.fn_name);
+    assert_eq!(sections.len(), 2, "{dump}");
+    assert_eq!(sections[0].fn_name, "first");
+    assert_eq!(sections[1].fn_name, "second");
+    for section in sections {
+        assert_eq!(section.crate_name, Some("sections"));
+        assert!(section.lines.iter().any(|line| line.starts_with("bb 0 {")));
+        assert!(section.lines.iter().any(|line| line.starts_with("bb0 at ")));
+    }
+}
+
+fn shared_generic_fixture(name: &str) -> Fixture {
+    Fixture::new(name)
+        .file(
+            "Cargo.toml",
+            &format!(
+                "{}\n[dependencies]\nshared = {{ path = \"shared\" }}\n",
+                package_manifest("app")
+            ),
+        )
+        .file("src/main.rs", "fn main() {\n    shared::ident(1u32);\n}\n")
+        .file(
+            "shared/Cargo.toml",
+            "[package]\nname = \"shared\"\nversion = \"0.1.0\"\nedition = \"2021\"\n",
+        )
+        .file(
+            "shared/src/lib.rs",
+            "pub fn ident<T>(t: T) -> T {\n    t\n}\n",
+        )
+}
+
+#[test]
+fn shared_functions_are_dumped_once() {
+    let fixture = shared_generic_fixture("dumpfmt_dedup");
+    run(fixture.cargo_rtool(&["-mir", "ident", "-outpath", "mir.txt"], &["--offline"]));
+
+    let dump = fixture.read("mir.txt");
+    let sections = dumpfmt::split_sections(&dump).unwrap();
+    let ident: Vec<_> = sections
+        .iter()
+        .filter(|section| section.fn_name == "shared::ident" || section.fn_name == "ident")
+        .collect();
+    assert_eq!(ident.len(), 2, "{dump}");
+    assert_eq!(ident[0].crate_name, Some("shared"));
+    assert_eq!(ident[0].dumped_in, None);
+    assert_eq!(ident[1].crate_name, Some("app"));
+    assert_eq!(ident[1].dumped_in, Some("shared"));
+    assert!(ident[1].lines.is_empty());
+    // The manifest the crates shared is not left next to the dump.
+    assert!(!fixture.path("mir.txt.manifest").exists());
+
+    run(fixture.cargo_rtool(
+        &["-mir", "ident", "-no-dedup", "-outpath", "mir.txt"],
+        &["--offline"],
+    ));
+    let dump = fixture.read("mir.txt");
+    let sections = dumpfmt::split_sections(&dump).unwrap();
+    assert!(
+        sections.iter().all(|section| section.dumped_in.is_none()),
+        "{dump}"
+    );
+}
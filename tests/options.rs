@@ -0,0 +1,107 @@
+#![feature(rustc_private)]
+
+extern crate rustc_driver;
+
+use rtool::RtoolCallback;
+use rtool::options::{self, OptionScope, RTOOL_ENV_VARS, RTOOL_OPTIONS};
+use std::path::Path;
+
+#[test]
+fn every_option_is_in_the_help() {
+    let help = options::help_text();
+    let markdown = options::help_markdown();
+    for option in RTOOL_OPTIONS {
+        assert!(
+            help.lines()
+                .any(|line| line.trim_start().starts_with(option.name)),
+            "{} missing from the help",
+            option.name
+        );
+        assert!(markdown.contains(&format!("| `{}", option.name)));
+    }
+    for var in RTOOL_ENV_VARS {
+        assert!(help.contains(var.name));
+        assert!(markdown.contains(var.name));
+    }
+}
+
+#[test]
+fn every_option_in_the_help_is_defined() {
+    for line in options::help_text().lines() {
+        let Some(flag) = line.strip_prefix("    -") else {
+            continue;
+        };
+        let name = format!("-{}", flag.split_whitespace().next().unwrap());
+        assert!(
+            options::find_option(&name).is_some(),
+            "{} is not defined",
+            name
+        );
+    }
+}
+
+#[test]
+fn every_rtool_option_is_parsed() {
+    let outpath = Path::new(env!("CARGO_TARGET_TMPDIR")).join("options_outpath.txt");
+    for option in RTOOL_OPTIONS
+        .iter()
+        .filter(|option| matches!(option.scope, OptionScope::Rtool | OptionScope::Binary))
+    {
+        // Some options exclude each other, so apply each one on its own.
+        let mut callback = RtoolCallback::default();
+        let res = match option.name {
+            "-outpath" => {
+                callback.set_option_value(option.name, outpath.to_string_lossy().into_owned())
+            }
+            "-mirphase" => callback.set_option_value(option.name, "built".to_string()),
+            "-color" => callback.set_option_value(option.name, "never".to_string()),
+            "-mircase" => callback.set_option_value(option.name, "insensitive".to_string()),
+            "-mirformat" => callback.set_option_value(option.name, "md".to_string()),
+            "-mirdefid" => callback.set_option_value(option.name, "0:5".to_string()),
+            "-mirat" => callback.set_option_value(option.name, "src/lib.rs:1".to_string()),
+            _ if option.value.is_some() => {
+                callback.set_option_value(option.name, "value".to_string())
+            }
+            _ => callback.enable_option(option.name),
+        };
+        assert_eq!(res, Ok(()), "{} is not parsed", option.name);
+    }
+}
+
+#[test]
+fn typos_get_suggestions() {
+    assert_eq!(options::suggest_option("-mirexcat"), Some("-mirexact"));
+    assert_eq!(options::suggest_option("-allmri"), Some("-allmir"));
+    assert_eq!(options::suggest_option("--crate-type"), None);
+    assert_eq!(options::suggest_option("-C"), None);
+    assert_eq!(options::suggest_option("-Zalways-encode-mir"), None);
+}
+
+#[test]
+fn fn_name_lists_are_split() {
+    let mut callback = RtoolCallback::default();
+    assert_eq!(
+        callback.set_option_value("-mir", " a, b ,c".to_string()),
+        Ok(())
+    );
+    assert_eq!(
+        callback.set_option_value("-mirexact", "HashMap::<K, V>::get,with\\,comma".to_string()),
+        Ok(())
+    );
+    assert_eq!(
+        callback.find_mir_targets(),
+        [
+            "-mirexact HashMap::<K, V>::get",
+            "-mirexact with,comma",
+            "-mir a",
+            "-mir b",
+            "-mir c"
+        ]
+    );
+    assert!(
+        callback
+            .set_option_value("-mir", "a,,b".to_string())
+            .is_err()
+    );
+    assert!(callback.set_option_value("-mir", " ".to_string()).is_err());
+}
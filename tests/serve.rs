@@ -0,0 +1,57 @@
+mod common;
+
+use common::Fixture;
+use serde_json::Value;
+use std::io::Write;
+use std::process::Stdio;
+
+#[test]
+fn serve_answers_requests_until_eof() {
+    let fixture = Fixture::new("serve").file(
+        "lib.rs",
+        "pub fn answer() -> u32 {\n    42\n}\n\nmod inner {\n    pub fn nested() {}\n}\n",
+    );
+
+    let mut child = fixture
+        .rtool(&["lib.rs", "--crate-type", "lib", "-serve"])
+        .stdin(Stdio::piped())
+        .stdout(Stdio::piped())
+        .stderr(Stdio::null())
+        .spawn()
+        .unwrap();
+    let requests = [
+        r#"{"jsonrpc":"2.0","id":1,"method":"dumpMir","params":{"def_path":"answer"}}"#,
+        r#"{"jsonrpc":"2.0","id":2,"method":"dumpMir","params":{"def_path":"inner::nested"}}"#,
+        r#"{"jsonrpc":"2.0","id":3,"method":"dumpMir","params":{"def_path":"missing"}}"#,
+        r#"{"jsonrpc":"2.0","id":4,"method":"getFindings","params":{}}"#,
+        // Notifications, without an id, get no response, even for an unknown method.
+        r#"{"jsonrpc":"2.0","method":"dumpMir","params":{"def_path":"answer"}}"#,
+        r#"{"jsonrpc":"2.0","method":"exit"}"#,
+        "not json",
+    ];
+    child
+        .stdin
+        .take()
+        .unwrap()
+        .write_all(requests.join("\n").as_bytes())
+        .unwrap();
+    let output = child.wait_with_output().unwrap();
+    assert!(output.status.success());
+
+    let responses: Vec<Value> = String::from_utf8(output.stdout)
+        .unwrap()
+        .lines()
+        .map(|line| serde_json::from_str(line).unwrap())
+        .collect();
+    assert_eq!(responses.len(), 5);
+
+    assert_eq!(responses[0]["id"], 1);
+    let mir = responses[0]["result"]["mir"].as_str().unwrap();
+    assert!(mir.starts_with("fn answer\n"), "{mir}");
+    assert!(mir.contains("const 42_u32"), "{mir}");
+
+    assert_eq!(responses[1]["result"]["def_path"], "inner::nested");
+    assert_eq!(responses[2]["error"]["code"], -32602);
+    assert_eq!(responses[3]["error"]["code"], -32601);
+    assert_eq!(responses[4]["error"]["code"], -32700);
+}
@@ -0,0 +1,1324 @@
+#![feature(rustc_private)]
+
+extern crate rustc_driver;
+extern crate rustc_middle;
+
+mod common;
+
+use common::{Fixture, package_manifest, run};
+use rtool::analysis::show_mir::{Display, DisplayWithTcx, MirFormat, mir_file_name};
+use rtool::utils::dumpfmt;
+use rtool::{RtoolCallback, findings, report_finding};
+use rustc_middle::mir::{
+    BackwardIncompatibleDropReason, BasicBlockData, Local, LocalDecls, Place, StatementKind,
+};
+use rustc_middle::ty::TyCtxt;
+use serde_json::Value;
+use std::process::{Command, Stdio};
+use std::sync::atomic::{AtomicBool, Ordering};
+
+#[test]
+fn tail_calls_are_displayed() {
+    let fixture = Fixture::new("show_mir_tail_call").file(
+        "lib.rs",
+        "#![feature(explicit_tail_calls)]\n#![allow(incomplete_features)]\n\n\
+         pub fn count_down(n: u32) -> u32 {\n    if n == 0 {\n        return 0;\n    }\n    \
+         become count_down(n - 1)\n}\n",
+    );
+
+    run(fixture.rtool(&[
+        "lib.rs",
+        "--crate-type",
+        "lib",
+        "-mirexact",
+        "count_down",
+        "-outpath",
+        "mir.txt",
+    ]));
+
+    let dump = fixture.read("mir.txt");
+    assert!(dump.contains("@ TailCall: count_down\n"), "{dump}");
+}
+
+#[test]
+fn all_mir_of_tail_calls_dumps_cleanly() {
+    let fixture = Fixture::new("show_mir_all_tail_call").file(
+        "lib.rs",
+        "#![feature(explicit_tail_calls)]\n#![allow(incomplete_features)]\n\n\
+         pub fn count_down(n: u32) -> u32 {\n    if n == 0 {\n        return 0;\n    }\n    \
+         become count_down(n - 1)\n}\n",
+    );
+
+    let mut cmd = fixture.rtool(&["lib.rs", "--crate-type", "lib", "-allmir"]);
+    cmd.env("RTOOL_LOG", "info");
+    let output = run(cmd);
+
+    let stdout = String::from_utf8_lossy(&output.stdout);
+    let stderr = String::from_utf8_lossy(&output.stderr);
+    assert!(stdout.contains("@ TailCall: count_down"), "{stdout}");
+    assert!(!stderr.contains("Skip"), "{stderr}");
+}
+
+const CONSTS_LIB: &str = "use std::sync::Mutex;\n\n\
+     pub static COUNTER: Mutex<u32> = Mutex::new(0);\n\
+     pub const LIMIT: usize = 2 + 2;\n\
+     pub static mut RAW: u8 = 0;\n\n\
+     pub fn limit() -> usize {\n    LIMIT\n}\n";
+
+/// The `fn` lines of a plain dump.
+fn fn_lines(dump: &str) -> Vec<&str> {
+    dump.lines()
+        .filter(|line| line.starts_with("fn "))
+        .collect()
+}
+
+#[test]
+fn mirconsts_shows_labeled_const_and_static_initializers() {
+    let fixture = Fixture::new("show_mir_consts").file("lib.rs", CONSTS_LIB);
+
+    run(fixture.rtool(&[
+        "lib.rs",
+        "--crate-type",
+        "lib",
+        "-mirconsts",
+        "-outpath",
+        "mir.txt",
+    ]));
+
+    let dump = fixture.read("mir.txt");
+    assert_eq!(
+        fn_lines(&dump),
+        ["fn COUNTER", "fn LIMIT", "fn RAW"],
+        "{dump}"
+    );
+    for label in [
+        "fn COUNTER\nstatic std::sync::Mutex<u32>\n",
+        "fn LIMIT\nconst usize\n",
+        "fn RAW\nstatic mut u8\n",
+    ] {
+        assert!(dump.contains(label), "{label} in {dump}");
+    }
+}
+
+#[test]
+fn allmir_shows_consts_only_when_asked() {
+    let fixture = Fixture::new("show_mir_allmir_consts").file("lib.rs", CONSTS_LIB);
+
+    let output = run(fixture.rtool(&["lib.rs", "--crate-type", "lib", "-allmir", "-nocolor"]));
+    let stdout = String::from_utf8_lossy(&output.stdout);
+    assert_eq!(fn_lines(&stdout), ["fn limit"], "{stdout}");
+
+    let output = run(fixture.rtool(&[
+        "lib.rs",
+        "--crate-type",
+        "lib",
+        "-allmir",
+        "-nocolor",
+        "-allmir-include-consts",
+    ]));
+    let stdout = String::from_utf8_lossy(&output.stdout);
+    assert_eq!(
+        fn_lines(&stdout),
+        ["fn COUNTER", "fn LIMIT", "fn RAW", "fn limit"],
+        "{stdout}"
+    );
+}
+
+#[test]
+fn all_mir_stops_when_stdout_is_closed() {
+    let fixture = Fixture::new("show_mir_all_closed_stdout").file(
+        "lib.rs",
+        "pub fn one() {}\n\npub fn two() {}\n\npub fn three() {}\n",
+    );
+
+    let mut cmd = fixture.rtool(&["lib.rs", "--crate-type", "lib", "-allmir"]);
+    cmd.env("RTOOL_LOG", "info")
+        .stdout(Stdio::piped())
+        .stderr(Stdio::piped());
+    let mut child = cmd.spawn().unwrap();
+    // Close the reading end before any MIR is written, as `| head` does once it is done.
+    drop(child.stdout.take());
+    let output = child.wait_with_output().unwrap();
+
+    let stderr = String::from_utf8_lossy(&output.stderr);
+    assert!(!stderr.contains("Skip"), "{stderr}");
+    assert!(!stderr.contains("panicked"), "{stderr}");
+}
+
+#[test]
+fn calls_show_the_callee() {
+    let fixture = Fixture::new("show_mir_calls").file(
+        "lib.rs",
+        "pub fn ident<T>(t: T) -> T {\n    t\n}\n\n\
+         pub fn direct() -> u32 {\n    ident(1u32)\n}\n\n\
+         pub fn indirect(f: fn(u32) -> u32) -> u32 {\n    f(1)\n}\n",
+    );
+
+    run(fixture.rtool(&[
+        "lib.rs",
+        "--crate-type",
+        "lib",
+        "-mirexact",
+        "direct",
+        "-mirexact",
+        "indirect",
+        "-outpath",
+        "mir.txt",
+    ]));
+
+    let dump = fixture.read("mir.txt");
+    assert!(
+        dump.contains("@ Call: ident::<u32> -> [return: bb1, unwind: continue]\n"),
+        "{dump}"
+    );
+    assert!(
+        dump.contains("@ Call: indirect fn(u32) -> u32 -> [return: bb1, unwind: continue]\n"),
+        "{dump}"
+    );
+}
+
+#[test]
+fn terminators_show_their_successors() {
+    let fixture = Fixture::new("show_mir_successors").file(
+        "lib.rs",
+        "pub fn pick(x: u32, v: Vec<u32>) -> u32 {\n    match x {\n        0 => 1,\n        \
+         7 => v[0],\n        _ => x,\n    }\n}\n",
+    );
+
+    run(fixture.rtool(&[
+        "lib.rs",
+        "--crate-type",
+        "lib",
+        "-mirexact",
+        "pick",
+        "-outpath",
+        "mir.txt",
+    ]));
+
+    let dump = fixture.read("mir.txt");
+    for successors in [
+        "@ SwitchInt -> [0: bb3, 7: bb2, otherwise: bb1]\n",
+        "@ Goto -> bb5\n",
+        "-> [return: bb4, unwind: bb7]\n",
+        "@ Drop(_2: std::vec::Vec<u32>) -> [return: bb6, unwind: bb8]\n",
+        "@ Drop(_2: std::vec::Vec<u32>) -> [return: bb8, unwind: terminate]\n",
+        "@ Return\n",
+        "\n(cleanup) bb 7 {\nCleanUp: true\n",
+        "\nbb 6 {\nCleanUp: false\n",
+    ] {
+        assert!(dump.contains(successors), "{successors} in {dump}");
+    }
+}
+
+#[test]
+fn drops_show_the_dropped_place_and_type() {
+    let fixture = Fixture::new("show_mir_drops").file(
+        "lib.rs",
+        "use std::sync::Mutex;\n\n\
+         pub struct Foo {\n    pub v: Vec<u8>,\n    pub w: Vec<u8>,\n}\n\n\
+         pub fn part(f: Foo) -> Vec<u8> {\n    f.v\n}\n\n\
+         pub fn lock(m: &Mutex<u32>) -> u32 {\n    let g = m.lock().unwrap();\n    *g\n}\n",
+    );
+
+    let output = run(fixture.rtool(&["lib.rs", "--crate-type", "lib", "-mir", "part,lock"]));
+
+    let stdout = String::from_utf8_lossy(&output.stdout);
+    for drop in [
+        "@ Drop((_1.1: std::vec::Vec<u8>): std::vec::Vec<u8>) -> [return: bb2, unwind: bb1]\n",
+        "@ Drop(_2: std::sync::MutexGuard<'_, u32>) -> [return: bb4, unwind: bb6]\n",
+    ] {
+        assert!(stdout.contains(drop), "{drop} in {stdout}");
+    }
+}
+
+#[test]
+fn unlisted_statement_kinds_are_displayed() {
+    // Removed from MIR before it is dumped, so build one.
+    let kind = StatementKind::BackwardIncompatibleDropHint {
+        place: Box::new(Place::from(Local::from_u32(1))),
+        reason: BackwardIncompatibleDropReason::Edition2024,
+    };
+    assert_eq!(kind.display(), " @ Unknown(BackwardIncompatibleDropHint)");
+}
+
+#[test]
+fn unlisted_rvalues_are_displayed() {
+    // Bodies using the MIR variants the display has no arm for yet.
+    let fixture = Fixture::new("show_mir_unknown_rvalue").file(
+        "lib.rs",
+        "#![feature(unsafe_binders)]\n#![allow(incomplete_features)]\n\n\
+         use std::unsafe_binder::wrap_binder;\n\n\
+         pub fn wrap(x: &i32) -> unsafe<'a> &'a i32 {\n    unsafe { wrap_binder!(x) }\n}\n",
+    );
+
+    let mut cmd = fixture.rtool(&[
+        "lib.rs",
+        "--crate-type",
+        "lib",
+        "-mirexact",
+        "wrap",
+        "-outpath",
+        "mir.txt",
+    ]);
+    cmd.env("RTOOL_LOG", "warn");
+    let output = run(cmd);
+
+    let dump = fixture.read("mir.txt");
+    assert!(dump.contains("@ Unknown(wrap_binder!("), "{dump}");
+    let stderr = String::from_utf8_lossy(&output.stderr);
+    assert_eq!(stderr.matches("Unknown rvalue").count(), 1, "{stderr}");
+}
+
+#[test]
+fn locals_show_their_source_names() {
+    let fixture = Fixture::new("show_mir_local_names").file(
+        "lib.rs",
+        "pub fn sum(first: u32, second: u32) -> u32 {\n    let total = first + second;\n    total\n}\n",
+    );
+
+    run(fixture.rtool(&[
+        "lib.rs",
+        "--crate-type",
+        "lib",
+        "-mirexact",
+        "sum",
+        "-outpath",
+        "mir.txt",
+    ]));
+
+    let dump = fixture.read("mir.txt");
+    assert!(dump.contains("_0:  @ u32 \n"), "{dump}");
+    assert!(dump.contains("_1:  @ u32 // first\n"), "{dump}");
+    assert!(dump.contains("_2:  @ u32 // second\n"), "{dump}");
+    assert!(dump.contains("_3:  @ u32 // total\n"), "{dump}");
+}
+
+#[test]
+fn regexes_match_the_whole_def_path() {
+    let fixture = Fixture::new("show_mir_regex").file(
+        "lib.rs",
+        "pub mod spin {\n    pub fn new() {}\n}\n\npub mod mutex {\n    pub fn new() {}\n}\n\n\
+         pub fn lock() {}\n",
+    );
+
+    run(fixture.rtool(&[
+        "lib.rs",
+        "--crate-type",
+        "lib",
+        "-mirregex",
+        "^spin::",
+        "-mirexact",
+        "lock",
+        "-outpath",
+        "mir.txt",
+    ]));
+
+    let dump = fixture.read("mir.txt");
+    assert!(dump.contains("fn spin::new\n"), "{dump}");
+    assert!(dump.contains("fn lock\n"), "{dump}");
+    assert!(!dump.contains("fn mutex::new\n"), "{dump}");
+}
+
+#[test]
+fn functions_matching_several_targets_are_shown_once() {
+    let fixture = Fixture::new("show_mir_once").file("lib.rs", "pub fn lock() {}\n");
+
+    run(fixture.rtool(&[
+        "lib.rs",
+        "--crate-type",
+        "lib",
+        "-mirexact",
+        "lock",
+        "-mir",
+        "lock",
+        "-mirregex",
+        "lock",
+        "-outpath",
+        "mir.txt",
+    ]));
+
+    let dump = fixture.read("mir.txt");
+    assert_eq!(dump.matches("fn lock\n").count(), 1, "{dump}");
+}
+
+#[test]
+fn mir_json_has_one_sorted_object_per_fn() {
+    let fixture = Fixture::new("show_mir_json")
+        .file("Cargo.toml", &package_manifest("json"))
+        .file(
+            "src/lib.rs",
+            "pub fn second_fn() -> u32 {\n    first_fn() + 1\n}\n\n\
+             pub fn first_fn() -> u32 {\n    1\n}\n",
+        );
+
+    run(fixture.cargo_rtool(
+        &["-mir", "_fn", "-mirjson", "-outpath", "mir.json"],
+        &["--offline"],
+    ));
+
+    let dump = fixture.read("mir.json");
+    let functions: Vec<Value> = dump
+        .lines()
+        .map(|line| serde_json::from_str(line).unwrap())
+        .collect();
+    let def_paths: Vec<&str> = functions
+        .iter()
+        .map(|function| function["def_path"].as_str().unwrap())
+        .collect();
+    assert_eq!(def_paths, ["first_fn", "second_fn"], "{dump}");
+    let second = &functions[1];
+    assert_eq!(second["crate"], "json");
+    assert!(second["def_id_index"].is_u64());
+    assert_eq!(second["locals"][0]["ty"], "u32");
+    let bb0 = &second["basic_blocks"][0];
+    assert_eq!(bb0["index"], 0);
+    assert!(
+        bb0["terminator"].as_str().unwrap().contains("first_fn"),
+        "{dump}"
+    );
+    assert!(
+        bb0["span"].as_str().unwrap().starts_with("src/lib.rs:"),
+        "{dump}"
+    );
+}
+
+#[test]
+fn mir_dot_has_one_digraph_per_fn() {
+    let fixture = Fixture::new("show_mir_dot").file(
+        "lib.rs",
+        "pub struct Noisy;\n\nimpl Drop for Noisy {\n    fn drop(&mut self) {}\n}\n\n\
+         pub fn branch(flag: bool, f: fn()) -> u32 {\n    let _noisy = Noisy;\n    f();\n    \
+         if flag { 1 } else { 2 }\n}\n",
+    );
+
+    run(fixture.rtool(&[
+        "lib.rs",
+        "--crate-type",
+        "lib",
+        "-mirexact",
+        "branch",
+        "-mirdot",
+        "-outpath",
+        "mir.dot",
+    ]));
+
+    let dot = fixture.read("mir.dot");
+    assert!(dot.starts_with("digraph \"branch\" {\n"), "{dot}");
+    assert_eq!(dot.matches("digraph").count(), 1, "{dot}");
+    assert!(dot.contains("[label=\"bb0\\n"), "{dot}");
+    assert!(dot.contains("SwitchInt"), "{dot}");
+    assert!(dot.contains("bb0 -> bb"), "{dot}");
+    assert!(dot.contains(", style=dashed];"), "{dot}");
+    assert!(dot.ends_with("}\n"), "{dot}");
+}
+
+#[test]
+fn mir_md_has_a_locals_table_and_block_code() {
+    let fixture = Fixture::new("show_mir_md").file(
+        "lib.rs",
+        "pub fn pick(flag: bool, pair: (u8, u8)) -> Option<u8> {\n    \
+         if flag { Some(pair.0) } else { None }\n}\n",
+    );
+
+    run(fixture.rtool(&[
+        "lib.rs",
+        "--crate-type",
+        "lib",
+        "-mirexact",
+        "pick",
+        "-mirformat",
+        "md",
+        "-outpath",
+        "mir.md",
+    ]));
+
+    let md = fixture.read("mir.md");
+    assert!(md.starts_with("### fn pick\n\n"), "{md}");
+    assert!(
+        md.contains("| local | name | type |\n| --- | --- | --- |\n"),
+        "{md}"
+    );
+    assert!(
+        md.contains("| \\_0 |  | std::option::Option&lt;u8&gt; |\n"),
+        "{md}"
+    );
+    assert!(md.contains("| \\_1 | flag | bool |\n"), "{md}");
+    assert!(md.contains("\n**bb0** at lib.rs:"), "{md}");
+    assert!(md.contains("\n\n```\nCleanUp: false\n"), "{md}");
+    assert!(md.contains("SwitchInt"), "{md}");
+}
+
+static NO_TERMINATOR_SHOWN: AtomicBool = AtomicBool::new(false);
+
+fn display_block_without_terminator(tcx: TyCtxt<'_>) {
+    let block = BasicBlockData::new(None, false);
+    let shown = block.display_with_tcx(tcx, LocalDecls::empty());
+    NO_TERMINATOR_SHOWN.store(shown.contains("<no terminator>"), Ordering::SeqCst);
+}
+
+#[test]
+fn blocks_without_terminator_are_displayed() {
+    // Such blocks only exist while a body is built, so build one inside a compiler session.
+    let fixture = Fixture::new("show_mir_no_terminator").file("lib.rs", "pub fn empty() {}\n");
+    let args: Vec<String> = [
+        "rtool",
+        fixture.path("lib.rs").to_str().unwrap(),
+        "--crate-type",
+        "lib",
+        "--out-dir",
+        fixture.path("target").to_str().unwrap(),
+    ]
+    .map(String::from)
+    .to_vec();
+
+    let mut callback = RtoolCallback::default();
+    callback.register_analysis("no_terminator", Box::new(display_block_without_terminator));
+    rustc_driver::run_compiler(&args, &mut callback);
+
+    assert!(NO_TERMINATOR_SHOWN.load(Ordering::SeqCst));
+}
+
+fn report_every_body(tcx: TyCtxt<'_>) {
+    for def_id in tcx.mir_keys(()) {
+        report_finding(format!("{} looks suspicious", tcx.def_path_str(*def_id)));
+    }
+}
+
+#[test]
+fn registered_analyses_count_their_findings() {
+    let fixture = Fixture::new("show_mir_findings")
+        .file("lib.rs", "pub fn first() {}\n\npub fn second() {}\n");
+    let args: Vec<String> = [
+        "rtool",
+        fixture.path("lib.rs").to_str().unwrap(),
+        "--crate-type",
+        "lib",
+        "--out-dir",
+        fixture.path("target").to_str().unwrap(),
+    ]
+    .map(String::from)
+    .to_vec();
+
+    let mut callback = RtoolCallback::default();
+    callback.register_analysis("report_every_body", Box::new(report_every_body));
+    rustc_driver::run_compiler(&args, &mut callback);
+
+    // No other test of this binary reports findings.
+    assert_eq!(findings(), 2);
+}
+
+#[test]
+fn all_mir_without_color_is_plain() {
+    let fixture =
+        Fixture::new("show_mir_no_color").file("lib.rs", "pub fn plain() -> u32 {\n    1\n}\n");
+
+    let mut cmd = fixture.rtool(&["lib.rs", "--crate-type", "lib", "-allmir", "-nocolor"]);
+    cmd.env_remove("NO_COLOR");
+    let flag = run(cmd);
+    let mut cmd = fixture.rtool(&["lib.rs", "--crate-type", "lib", "-allmir"]);
+    cmd.env("NO_COLOR", "1");
+    let env = run(cmd);
+    // stdout isn't a terminal here.
+    let mut cmd = fixture.rtool(&["lib.rs", "--crate-type", "lib", "-allmir"]);
+    cmd.env_remove("NO_COLOR");
+    let auto = run(cmd);
+
+    for output in [flag, env, auto] {
+        let stdout = String::from_utf8_lossy(&output.stdout);
+        assert!(stdout.contains("fn plain\n"), "{stdout}");
+        assert!(!stdout.contains('\x1b'), "{stdout}");
+    }
+}
+
+#[test]
+fn only_color_always_colors_files() {
+    let fixture =
+        Fixture::new("show_mir_color").file("lib.rs", "pub fn painted() -> u32 {\n    1\n}\n");
+    let dump = |color: &str| {
+        let mut args = vec!["lib.rs", "--crate-type", "lib", "-mir", "painted"];
+        args.extend(["-outpath", "mir.txt", "-color", color]);
+        run(fixture.rtool(&args));
+        fixture.read("mir.txt")
+    };
+
+    let colored = dump("always");
+    assert!(colored.contains("\x1b["), "{colored}");
+    assert!(colored.contains("fn painted"), "{colored}");
+    for color in ["auto", "never"] {
+        let plain = dump(color);
+        assert!(plain.contains("fn painted\n"), "{plain}");
+        assert!(!plain.contains('\x1b'), "{plain}");
+    }
+
+    let output = fixture
+        .rtool(&[
+            "lib.rs",
+            "--crate-type",
+            "lib",
+            "-allmir",
+            "-color",
+            "always",
+        ])
+        .env("NO_COLOR", "1")
+        .output()
+        .unwrap();
+    let stdout = String::from_utf8_lossy(&output.stdout);
+    assert!(stdout.contains("\x1b["), "{stdout}");
+}
+
+#[test]
+fn all_mir_is_reproducible() {
+    let fixture = Fixture::new("show_mir_reproducible").file(
+        "lib.rs",
+        "pub fn zeta() -> u32 {\n    alpha() + 1\n}\n\n\
+         pub fn alpha() -> u32 {\n    1\n}\n\n\
+         pub mod inner {\n    pub fn beta() {}\n}\n",
+    );
+
+    let dumps: Vec<Vec<u8>> = (0..2)
+        .map(|_| {
+            let cmd = fixture.rtool(&["lib.rs", "--crate-type", "lib", "-allmir", "-nocolor"]);
+            run(cmd).stdout
+        })
+        .collect();
+
+    assert_eq!(dumps[0], dumps[1]);
+    let dump = String::from_utf8_lossy(&dumps[0]);
+    let alpha = dump.find("fn alpha\n").expect("alpha is dumped");
+    let beta = dump.find("fn inner::beta\n").expect("beta is dumped");
+    let zeta = dump.find("fn zeta\n").expect("zeta is dumped");
+    assert!(alpha < beta && beta < zeta, "{dump}");
+}
+
+#[test]
+fn mir_stats_list_the_biggest_fns_first() {
+    let fixture = Fixture::new("show_mir_stats").file(
+        "lib.rs",
+        "pub fn small() {}\n\n\
+         pub fn big(flag: bool, name: String) -> usize {\n    \
+         if flag { small(); name.len() } else { 2 }\n}\n",
+    );
+
+    let output = run(fixture.rtool(&["lib.rs", "--crate-type", "lib", "-mirstats"]));
+
+    let stdout = String::from_utf8_lossy(&output.stdout);
+    let lines: Vec<&str> = stdout.lines().collect();
+    assert_eq!(lines.len(), 3, "{stdout}");
+    assert_eq!(
+        lines[0],
+        "big blocks=9 statements=9 calls=2 drops=2 cleanup_blocks=2 locals=6"
+    );
+    assert_eq!(
+        lines[1],
+        "small blocks=1 statements=1 calls=0 drops=0 cleanup_blocks=0 locals=1"
+    );
+    assert_eq!(
+        lines[2],
+        "total of 2 fns in lib: blocks=10 statements=10 calls=2 drops=2 cleanup_blocks=2 locals=7"
+    );
+}
+
+#[test]
+fn mir_stats_csv_go_to_the_outpath() {
+    let fixture = Fixture::new("show_mir_stats_csv").file(
+        "lib.rs",
+        "pub struct Pair<A, B>(A, B);\n\n\
+         impl<A, B> Pair<A, B> {\n    pub fn first(self) -> A {\n        self.0\n    }\n}\n",
+    );
+
+    run(fixture.rtool(&[
+        "lib.rs",
+        "--crate-type",
+        "lib",
+        "-mirstatscsv",
+        "-outpath",
+        "stats.csv",
+    ]));
+
+    let csv = fixture.read("stats.csv");
+    let lines: Vec<&str> = csv.lines().collect();
+    assert_eq!(
+        lines[0],
+        "crate,fn,blocks,statements,calls,drops,cleanup_blocks,locals"
+    );
+    assert!(
+        lines[1..]
+            .iter()
+            .any(|line| line.starts_with("lib,\"Pair::<A, B>::first\",")),
+        "{csv}"
+    );
+    assert!(!csv.contains("total"), "{csv}");
+}
+
+#[test]
+fn promoted_bodies_follow_their_fn() {
+    let fixture = Fixture::new("show_mir_promoted")
+        .file("lib.rs", "pub fn answer() -> &'static u32 {\n    &42\n}\n");
+    let args = [
+        "lib.rs",
+        "--crate-type",
+        "lib",
+        "-mirexact",
+        "answer",
+        "-outpath",
+        "mir.txt",
+    ];
+
+    run(fixture.rtool(&args));
+    let dump = fixture.read("mir.txt");
+    assert!(!dump.lines().any(|line| line == "promoted[0]"), "{dump}");
+
+    run(fixture.rtool(&[&args[..], &["-mirpromoted"]].concat()));
+    let dump = fixture.read("mir.txt");
+    let sections = dumpfmt::split_sections(&dump).unwrap();
+    assert_eq!(sections.len(), 1, "{dump}");
+    let lines = &sections[0].lines;
+    let promoted = lines.iter().position(|line| *line == "promoted[0]");
+    let promoted = promoted.expect("the promoted body is shown");
+    assert!(
+        lines[promoted..]
+            .iter()
+            .any(|line| line.contains("const 42_u32")),
+        "{dump}"
+    );
+    assert!(
+        lines[promoted..]
+            .iter()
+            .any(|line| line.contains(" at lib.rs:")),
+        "{dump}"
+    );
+}
+
+/// The basic blocks of a plain section, with the number of statements of each.
+fn block_sizes(lines: &[&str]) -> Vec<usize> {
+    let mut sizes = vec![];
+    let mut size = None;
+    for line in lines {
+        if line.trim_start_matches("(cleanup) ").starts_with("bb ") && line.ends_with(" {") {
+            size = Some(0);
+        } else if *line == "}" {
+            // The last line of a block is its terminator.
+            sizes.extend(size.take().map(|size: usize| size - 1));
+        } else if let Some(size) = size.as_mut()
+            && line.starts_with("    ")
+        {
+            *size += 1;
+        }
+    }
+    sizes
+}
+
+#[test]
+fn spans_cover_every_block_and_statement() {
+    let fixture = Fixture::new("show_mir_spans").file(
+        "lib.rs",
+        "pub fn call(name: String, f: fn()) -> usize {\n    f();\n    name.len()\n}\n",
+    );
+    let args = [
+        "lib.rs",
+        "--crate-type",
+        "lib",
+        "-mirexact",
+        "call",
+        "-outpath",
+        "mir.txt",
+    ];
+
+    run(fixture.rtool(&args));
+    let dump = fixture.read("mir.txt");
+    let sections = dumpfmt::split_sections(&dump).unwrap();
+    let sizes = block_sizes(&sections[0].lines);
+    assert!(sizes.contains(&0), "{dump}");
+    for block in 0..sizes.len() {
+        let prefix = format!("bb{} at lib.rs:", block);
+        assert!(dump.lines().any(|line| line.starts_with(&prefix)), "{dump}");
+    }
+
+    run(fixture.rtool(&[&args[..], &["-mirspans"]].concat()));
+    let dump = fixture.read("mir.txt");
+    let sections = dumpfmt::split_sections(&dump).unwrap();
+    let spans = dump.lines().filter(|line| line.contains(" at lib.rs:"));
+    let sizes = block_sizes(&sections[0].lines);
+    assert_eq!(
+        spans.count(),
+        sizes.iter().map(|size| size + 1).sum::<usize>(),
+        "{dump}"
+    );
+    for (block, size) in sizes.iter().enumerate() {
+        let prefix = format!("bb{}[{}] at lib.rs:", block, size);
+        assert!(dump.lines().any(|line| line.starts_with(&prefix)), "{dump}");
+    }
+}
+
+#[test]
+fn source_lines_start_each_block() {
+    let fixture = Fixture::new("show_mir_source").file(
+        "lib.rs",
+        "macro_rules! double {\n    ($x:expr) => {\n        $x * 2\n    };\n}\n\n\
+         pub fn quadruple(x: u32) -> u32 {\n    let y = double!(x);\n    y + y\n}\n",
+    );
+
+    run(fixture.rtool(&[
+        "lib.rs",
+        "--crate-type",
+        "lib",
+        "-mirexact",
+        "quadruple",
+        "-mirsource",
+        "-outpath",
+        "mir.txt",
+    ]));
+
+    let dump = fixture.read("mir.txt");
+    let lines: Vec<&str> = dump.lines().collect();
+    let first = lines.iter().position(|line| *line == "bb 0 {").unwrap();
+    assert_eq!(lines[first + 1], "    //     let y = double!(x);", "{dump}");
+    assert!(lines.contains(&"    //     y + y"), "{dump}");
+    assert!(!dump.contains("$x * 2"), "{dump}");
+    assert!(dumpfmt::split_sections(&dump).is_ok());
+}
+
+#[test]
+fn switch_values_are_decoded() {
+    let fixture = Fixture::new("show_mir_switch_values").file(
+        "lib.rs",
+        "pub enum Op {\n    Add = 3,\n    Neg = -1,\n    Nop,\n}\n\n\
+         pub fn eval(op: Op, b: bool, c: char, i: i8) -> i32 {\n    \
+         let x = match op {\n        Op::Add => 1,\n        Op::Neg => 2,\n        Op::Nop => 3,\n    };\n    \
+         let y = if b { 1 } else { 0 };\n    \
+         let z = match c {\n        'a' => 1,\n        _ => 0,\n    };\n    \
+         let w = match i {\n        -1 => 1,\n        5 => 2,\n        _ => 0,\n    };\n    \
+         x + y + z + w\n}\n",
+    );
+
+    let output = run(fixture.rtool(&["lib.rs", "--crate-type", "lib", "-mirexact", "eval"]));
+
+    let stdout = String::from_utf8_lossy(&output.stdout);
+    let switches: Vec<&str> = stdout
+        .lines()
+        .filter_map(|line| {
+            line.split_once(" @ SwitchInt -> ")
+                .map(|(_, targets)| targets)
+        })
+        .collect();
+    assert_eq!(
+        switches,
+        [
+            "[Add: bb4, Neg: bb3, Nop: bb2, otherwise: bb1]",
+            "[false: bb7, otherwise: bb6]",
+            "['a': bb10, otherwise: bb9]",
+            "[-1: bb14, 5: bb13, otherwise: bb12]"
+        ],
+        "{stdout}"
+    );
+}
+
+#[test]
+fn generic_fns_show_their_generics_and_instances() {
+    let fixture = Fixture::new("show_mir_instances").file(
+        "lib.rs",
+        "pub fn ident<T>(t: T) -> T {\n    t\n}\n\n\
+         pub fn direct() -> u32 {\n    ident(1u32) + ident(2u64) as u32\n}\n\n\
+         pub fn unused<const N: usize, T: Copy>(t: [T; N]) -> T {\n    t[0]\n}\n",
+    );
+    let rtool = |instances: bool| {
+        let mut args = vec![
+            "lib.rs",
+            "--crate-type",
+            "lib",
+            "-mir",
+            "ident",
+            "-mir",
+            "unused",
+        ];
+        if instances {
+            args.push("-mirinstances");
+        }
+        let stdout = run(fixture.rtool(&args)).stdout;
+        let sections = dumpfmt::split_sections(&String::from_utf8_lossy(&stdout))
+            .unwrap()
+            .iter()
+            .map(|section| section.fn_name.to_string())
+            .collect::<Vec<_>>();
+        (String::from_utf8_lossy(&stdout).into_owned(), sections)
+    };
+
+    let (dump, sections) = rtool(false);
+    assert_eq!(sections, ["ident", "unused"]);
+    assert!(dump.contains("fn ident\ngenerics <T>\n"), "{dump}");
+    assert!(
+        dump.contains("fn unused\ngenerics <const N, T>\n"),
+        "{dump}"
+    );
+
+    let (dump, sections) = rtool(true);
+    assert_eq!(
+        sections,
+        ["ident", "ident::<u32>", "ident::<u64>", "unused"]
+    );
+    let instance = &dump[dump.find("fn ident::<u64>\n").unwrap()..];
+    assert!(!instance.starts_with("fn ident::<u64>\ngenerics"), "{dump}");
+    assert!(instance.contains("_0:  @ u64"), "{dump}");
+}
+
+#[test]
+fn cfg_shows_dominators_and_loop_headers() {
+    let fixture = Fixture::new("show_mir_cfg").file(
+        "lib.rs",
+        "pub fn spin(n: u32) -> u32 {\n    let mut i = 0;\n    while i < n {\n        \
+         i += 1;\n    }\n    i\n}\n",
+    );
+
+    let output = run(fixture.rtool(&[
+        "lib.rs",
+        "--crate-type",
+        "lib",
+        "-mirexact",
+        "spin",
+        "-mircfg",
+    ]));
+
+    let stdout = String::from_utf8_lossy(&output.stdout);
+    let cfg: Vec<&str> = stdout
+        .lines()
+        .filter(|line| line.contains(" idom ") || line.ends_with(" entry"))
+        .collect();
+    assert_eq!(
+        cfg,
+        [
+            "bb0 entry",
+            "bb1 idom bb0 loop-header",
+            "bb2 idom bb1",
+            "bb3 idom bb2",
+            "bb4 idom bb1"
+        ],
+        "{stdout}"
+    );
+}
+
+#[test]
+fn mir_list_has_one_line_per_fn_and_a_count() {
+    let fixture = Fixture::new("show_mir_list").file(
+        "lib.rs",
+        "pub fn open_file() {}\n\npub mod net {\n    pub fn open_socket() {}\n}\n\n\
+         pub fn close() {}\n",
+    );
+
+    run(fixture.rtool(&[
+        "lib.rs",
+        "--crate-type",
+        "lib",
+        "-mirlist",
+        "open",
+        "-outpath",
+        "list.txt",
+    ]));
+
+    let list = fixture.read("list.txt");
+    let lines: Vec<&str> = list.lines().collect();
+    assert_eq!(lines.len(), 3, "{list}");
+    assert!(
+        lines[0].starts_with("net::open_socket blocks=1 at lib.rs:4 DefId("),
+        "{list}"
+    );
+    assert!(
+        lines[1].starts_with("open_file blocks=1 at lib.rs:1 DefId("),
+        "{list}"
+    );
+    assert_eq!(lines[2], "2 fns matched");
+}
+
+#[test]
+fn all_mir_filters_by_module() {
+    let fixture = Fixture::new("show_mir_all_filter").file(
+        "lib.rs",
+        "pub struct Outer;\n\n\
+         pub mod drivers {\n    pub trait Probe {\n        fn probe(&self);\n    }\n\n    \
+         impl Probe for u32 {\n        fn probe(&self) {}\n    }\n\n    \
+         impl crate::Outer {\n        pub fn attach(&self) {}\n    }\n\n    \
+         pub fn init() {}\n}\n\n\
+         pub mod net {\n    pub fn init() {}\n}\n\n\
+         pub mod fs {\n    pub fn init() {}\n}\n",
+    );
+
+    let mut cmd = fixture.rtool(&[
+        "lib.rs",
+        "--crate-type",
+        "lib",
+        "-allmir",
+        "-nocolor",
+        "-allmir-filter",
+        "drivers",
+        "-allmir-filter",
+        "fs::",
+    ]);
+    cmd.env_remove("NO_COLOR");
+    let output = run(cmd);
+
+    let stdout = String::from_utf8_lossy(&output.stdout);
+    let fns: Vec<&str> = stdout
+        .lines()
+        .filter(|line| line.starts_with("fn "))
+        .collect();
+    assert_eq!(
+        fns,
+        [
+            "fn <u32 as drivers::Probe>::probe",
+            "fn drivers::<impl Outer>::attach",
+            "fn drivers::init",
+            "fn fs::init"
+        ],
+        "{stdout}"
+    );
+}
+
+#[test]
+fn mir_phase_selects_the_body() {
+    let fixture = Fixture::new("show_mir_phase").file(
+        "lib.rs",
+        "pub fn pick(x: Option<u32>) -> u32 {\n    \
+         match x {\n        Some(v) if x.is_some() => v,\n        _ => 0,\n    }\n}\n",
+    );
+    let dump_in = |phase: &str| {
+        let mut cmd = fixture.rtool(&[
+            "lib.rs",
+            "--crate-type",
+            "lib",
+            "-mirexact",
+            "pick",
+            "-mirexact",
+            "std::option::Option::<T>::is_some",
+            "-mirphase",
+            phase,
+        ]);
+        cmd.env("RTOOL_LOG", "info");
+        run(cmd)
+    };
+
+    let built = dump_in("built");
+    let stdout = String::from_utf8_lossy(&built.stdout);
+    assert!(stdout.contains(" @ FalseEdge -> [real: "), "{stdout}");
+    assert!(stdout.contains(" @ PlaceMention\n"), "{stdout}");
+    let stderr = String::from_utf8_lossy(&built.stderr);
+    assert!(
+        stderr.contains("Skip std::option::Option::<T>::is_some: the built MIR of other crates"),
+        "{stderr}"
+    );
+
+    let optimized = dump_in("optimized");
+    let stdout = String::from_utf8_lossy(&optimized.stdout);
+    assert!(stdout.contains("fn pick\n"), "{stdout}");
+    assert!(!stdout.contains(" @ FalseEdge"), "{stdout}");
+}
+
+#[test]
+fn nested_bodies_go_along_with_their_fn() {
+    let fixture = Fixture::new("show_mir_nested").file(
+        "lib.rs",
+        "pub fn handler() -> u32 {\n    fn helper() -> u32 {\n        1\n    }\n    \
+         let add = |x: u32| x + helper();\n    add(const { 2 })\n}\n\n\
+         pub fn other() -> u32 {\n    let one = || 1;\n    one()\n}\n",
+    );
+
+    run(fixture.rtool(&[
+        "lib.rs",
+        "--crate-type",
+        "lib",
+        "-mirregex",
+        "^handler$",
+        "-mirregex",
+        r"handler::\{closure#0\}",
+        "-outpath",
+        "mir.txt",
+    ]));
+
+    let dump = fixture.read("mir.txt");
+    let fns: Vec<&str> = dumpfmt::split_sections(&dump)
+        .unwrap()
+        .iter()
+        .map(|section| section.fn_name)
+        .collect();
+    assert_eq!(
+        fns,
+        ["handler", "handler::{closure#0}", "handler::{constant#0}"],
+        "{dump}"
+    );
+}
+
+#[test]
+fn mir_at_shows_the_bodies_spanning_a_line() {
+    let fixture = Fixture::new("show_mir_at").file(
+        "lib.rs",
+        "pub fn first() -> u32 {\n    1\n}\n\n\
+         pub fn second() -> u32 {\n    let add = |x: u32| x + 1;\n    add(first())\n}\n",
+    );
+    let fns_at = |location: &str| {
+        let mut cmd = fixture.rtool(&["lib.rs", "--crate-type", "lib", "-mirat", location]);
+        cmd.env("RTOOL_LOG", "info");
+        let output = run(cmd);
+        let stdout = String::from_utf8_lossy(&output.stdout).into_owned();
+        let stderr = String::from_utf8_lossy(&output.stderr).into_owned();
+        let fns: Vec<String> = dumpfmt::split_sections(&stdout)
+            .unwrap()
+            .iter()
+            .map(|section| section.fn_name.to_string())
+            .collect();
+        (fns, stderr)
+    };
+
+    assert_eq!(fns_at("lib.rs:2").0, ["first"]);
+    let absolute = format!("{}:6", fixture.path("lib.rs").display());
+    assert_eq!(fns_at(&absolute).0, ["second", "second::{closure#0}"]);
+    let (fns, stderr) = fns_at("lib.rs:4");
+    assert!(fns.is_empty());
+    assert!(stderr.contains("No fn matched -mirat lib.rs:4"), "{stderr}");
+    let (fns, stderr) = fns_at("other.rs:1");
+    assert!(fns.is_empty());
+    assert!(
+        stderr.contains("other.rs is not part of this crate"),
+        "{stderr}"
+    );
+}
+
+#[test]
+fn mir_at_a_const_matches_nothing_without_mirconsts() {
+    let fixture = Fixture::new("show_mir_at_const").file("lib.rs", "pub const X: u32 = 1;\n");
+
+    let output = fixture
+        .rtool(&[
+            "lib.rs",
+            "--crate-type",
+            "lib",
+            "-mirat",
+            "lib.rs:1",
+            "-strict-match",
+        ])
+        .output()
+        .unwrap();
+
+    let stdout = String::from_utf8_lossy(&output.stdout);
+    let stderr = String::from_utf8_lossy(&output.stderr);
+    assert!(!output.status.success(), "{stderr}");
+    assert!(!stdout.contains("fn X"), "{stdout}");
+    assert!(stderr.contains("No fn matched -mirat lib.rs:1"), "{stderr}");
+}
+
+#[test]
+fn targets_matching_no_fn_are_warned_about() {
+    let fixture = Fixture::new("unmatched_targets").file("lib.rs", "pub fn handler() {}\n");
+    let rtool = |strict: bool| {
+        let mut args = vec!["lib.rs", "--crate-type", "lib"];
+        args.extend(["-mirexact", "handler", "-mir", "handlr"]);
+        if strict {
+            args.push("-strict-match");
+        }
+        let output = fixture.rtool(&args).output().unwrap();
+        let stdout = String::from_utf8_lossy(&output.stdout).into_owned();
+        let stderr = String::from_utf8_lossy(&output.stderr).into_owned();
+        (output.status.success(), stdout, stderr)
+    };
+
+    let (success, stdout, stderr) = rtool(false);
+    assert!(success, "{stderr}");
+    assert!(stdout.contains("fn handler"), "{stdout}");
+    assert!(stderr.contains("No fn matched -mir handlr"), "{stderr}");
+    assert!(!stderr.contains("No fn matched -mirexact"), "{stderr}");
+
+    // The compile completes, the bodies found are still shown.
+    let (success, stdout, stderr) = rtool(true);
+    assert!(!success);
+    assert!(stdout.contains("fn handler"), "{stdout}");
+    assert!(stderr.contains("No fn matched -mir handlr"), "{stderr}");
+}
+
+#[test]
+fn mir_file_names_are_sanitized() {
+    assert_eq!(
+        mir_file_name("lock", "<impl Drop for Guard<'_>>::drop", MirFormat::Plain),
+        "lock._impl_Drop_for_Guard_____.drop.txt"
+    );
+    assert_eq!(
+        mir_file_name("lock", "spin::new", MirFormat::Dot),
+        "lock.spin.new.dot"
+    );
+
+    let long = format!("module::{}", "very_long_name_".repeat(40));
+    let name = mir_file_name("lock", &long, MirFormat::Json);
+    assert!(name.len() <= 205, "{name}");
+    assert!(name.ends_with(".json"), "{name}");
+    assert_ne!(
+        name,
+        mir_file_name("lock", &format!("{}x", long), MirFormat::Json)
+    );
+}
+
+#[test]
+fn assigned_constants_are_decoded() {
+    let fixture = Fixture::new("show_mir_constants").file(
+        "lib.rs",
+        "pub const N: usize = 4;\n\n\
+         pub fn consts(a: i32) -> usize {\n    \
+         let _b = a - -7;\n    \
+         let _t = true;\n    \
+         let _c = 'x';\n    \
+         let _s = \"a string literal long enough to be cut short in the dump\";\n    \
+         let _r = [0u8; 16];\n    \
+         let _f = consts;\n    \
+         N\n}\n",
+    );
+
+    let output = run(fixture.rtool(&["lib.rs", "--crate-type", "lib", "-mirexact", "consts"]));
+
+    let stdout = String::from_utf8_lossy(&output.stdout);
+    for shown in [
+        "@ BinaryOp: SubWithOverflow(copy _3, const -7_i32)",
+        "@ Use: const true",
+        "@ Use: const 'x'",
+        "@ Use: const \"a string literal long enough to be cut short in...",
+        "@ Repeat: [const 0_u8; 16]",
+        "@ Use: consts\n",
+        "@ Use: const <unevaluated>",
+    ] {
+        assert!(stdout.contains(shown), "{shown} in {stdout}");
+    }
+    assert!(!stdout.contains("@ Use: copy"), "{stdout}");
+}
+
+#[test]
+fn mircase_insensitive_ignores_case_of_fuzzy_targets() {
+    let fixture = Fixture::new("show_mir_case").file(
+        "lib.rs",
+        "pub fn IrqHandler() {}\n\n\
+         pub fn irq_handler() {}\n\n\
+         pub fn IRQHandler_entry() {}\n\n\
+         pub fn other() {}\n",
+    );
+    let fns = |stdout: &str| -> Vec<String> {
+        stdout
+            .lines()
+            .filter_map(|line| line.strip_prefix("fn "))
+            .map(str::to_string)
+            .collect()
+    };
+
+    let output = run(fixture.rtool(&["lib.rs", "--crate-type", "lib", "-mir", "IrqHandler"]));
+    assert_eq!(
+        fns(&String::from_utf8_lossy(&output.stdout)),
+        ["IrqHandler"]
+    );
+
+    let output = run(fixture.rtool(&[
+        "lib.rs",
+        "--crate-type",
+        "lib",
+        "-mir",
+        "irqhandler",
+        "-mircase",
+        "insensitive",
+        "-mirexact",
+        "irqhandler",
+    ]));
+    assert_eq!(
+        fns(&String::from_utf8_lossy(&output.stdout)),
+        ["IRQHandler_entry", "IrqHandler"]
+    );
+    let stderr = String::from_utf8_lossy(&output.stderr);
+    assert!(
+        stderr.contains("Fuzzy match target, case insensitive: [\"irqhandler\"]"),
+        "{stderr}"
+    );
+    // -mirexact stays case sensitive.
+    assert!(
+        stderr.contains("No fn matched -mirexact irqhandler"),
+        "{stderr}"
+    );
+}
+
+#[test]
+fn trailing_options_require_their_values() {
+    let fixture = Fixture::new("show_mir_trailing_options").file("lib.rs", "pub fn f() {}\n");
+
+    for (option, value) in [
+        ("-mir", "fn_name"),
+        ("-mirexact", "fn_name"),
+        ("-outpath", "path"),
+    ] {
+        // Not `Fixture::rtool`, which ends the arguments with --out-dir.
+        let output = Command::new(env!("CARGO_BIN_EXE_rtool"))
+            .current_dir(fixture.path(""))
+            .args(["lib.rs", "--crate-type", "lib", option])
+            .env_remove("RTOOL_LOG")
+            .output()
+            .unwrap();
+        assert!(!output.status.success(), "{option}");
+        let stderr = String::from_utf8_lossy(&output.stderr);
+        assert!(
+            stderr.contains(&format!("{} requires {}.", option, value)),
+            "{stderr}"
+        );
+        assert!(!stderr.contains("Start analysis"), "{stderr}");
+    }
+
+    // A value can't be another option.
+    let output = fixture
+        .rtool(&[
+            "lib.rs",
+            "--crate-type",
+            "lib",
+            "-mir",
+            "-outpath",
+            "mir.txt",
+        ])
+        .output()
+        .unwrap();
+    assert!(!output.status.success());
+    let stderr = String::from_utf8_lossy(&output.stderr);
+    assert!(
+        stderr.contains("Invalid fn_name for -mir: -outpath"),
+        "{stderr}"
+    );
+    assert!(!stderr.contains("Start analysis"), "{stderr}");
+}
+
+#[test]
+fn mirdefid_picks_a_fn_by_its_def_id() {
+    let fixture = Fixture::new("show_mir_def_id").file(
+        "lib.rs",
+        "pub struct S<T>(T);\n\n\
+         impl S<u8> {\n    pub fn f(&self) -> u8 {\n        1\n    }\n}\n\n\
+         impl S<u16> {\n    pub fn f(&self) -> u8 {\n        2\n    }\n}\n\n\
+         pub fn fxx() {}\n",
+    );
+    let args = |extra: &[&'static str]| {
+        let mut args = vec!["lib.rs", "--crate-type", "lib"];
+        args.extend(extra);
+        args
+    };
+    let fns = |output: &std::process::Output| -> Vec<String> {
+        String::from_utf8_lossy(&output.stdout)
+            .lines()
+            .filter_map(|line| line.strip_prefix("fn "))
+            .map(str::to_string)
+            .collect()
+    };
+
+    // The fn of the first impl block is DefId(0:8 ~ lib::{impl#0}::f).
+    let output = run(fixture.rtool(&args(&["-mirdefid", "0:8"])));
+    assert_eq!(fns(&output), ["S::<u8>::f"]);
+    let stderr = String::from_utf8_lossy(&output.stderr);
+    assert!(!stderr.contains("by DefId"), "{stderr}");
+
+    // -mirexact still matches a part of the DefId for now, with a warning.
+    let output = run(fixture.rtool(&args(&["-mirexact", "{impl#1}", "-mirexact", "fxx"])));
+    assert_eq!(fns(&output), ["S::<u16>::f", "fxx"]);
+    let stderr = String::from_utf8_lossy(&output.stderr);
+    assert!(
+        stderr.contains("-mirexact {impl#1} matched S::<u16>::f by DefId"),
+        "{stderr}"
+    );
+    assert!(!stderr.contains("-mirexact fxx matched"), "{stderr}");
+
+    let output = run(fixture.rtool(&args(&["-mirdefid", "0:99"])));
+    let stderr = String::from_utf8_lossy(&output.stderr);
+    assert!(stderr.contains("No fn matched -mirdefid 0:99"), "{stderr}");
+}